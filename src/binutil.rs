@@ -1,8 +1,9 @@
-use std::{fmt::Debug, io::SeekFrom};
+use std::{cell::RefCell, fmt::Debug, io::SeekFrom};
 
 use anyhow::{Result, anyhow, bail, ensure};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use indexmap::IndexMap;
+use log::warn;
 use vivibin::{
     CanRead, CanReadVec, CanWrite, CanWriteBox, CanWriteSlice, CanWriteSliceWithArgs,
     CanWriteWithArgs, EndianSpecific, Endianness, HeapCategory, HeapToken, ReadDomain, Readable,
@@ -10,40 +11,130 @@ use vivibin::{
 };
 
 use crate::{
-    RelDeclaration, SymbolDeclaration, SymbolName,
-    elf::{Relocation, Symbol},
+    BOX_SYMBOL_PREFIX, DEBUG_RELOCATION_TAG, RelDeclaration, STRING_SYMBOL_PREFIX,
+    SymbolDeclaration, SymbolName,
+    elf::{R_PPC_ADDR32, Relocation, STB_GLOBAL, Symbol, SymbolTable},
+    error::PaintelfError,
     scoped_reader_pos,
-    util::{pointer::Pointer, read_string},
+    util::{edit_distance, pointer::{Pointer, SymbolPointer}, read_string},
 };
 
 // deserializing
+
+/// Smallest size an element of a serialized array can have, used to reject absurd counts
+/// before reading them.
+pub const MIN_ELEMENT_SIZE: u64 = 4;
+
+/// Makes sure that `count` elements starting at `ptr` can fit into the section behind `reader`,
+/// assuming each element is at least [`MIN_ELEMENT_SIZE`] bytes big.
+pub fn ensure_vec_fits(reader: &mut impl Reader, ptr: Pointer, count: u32) -> Result<()> {
+    let start_pos = reader.stream_position()?;
+    let section_size = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(start_pos))?;
+    
+    let end = u64::from(ptr) + u64::from(count) * MIN_ELEMENT_SIZE;
+    ensure!(end <= section_size, "Vec count {count} at 0x{:x} exceeds section size (0x{section_size:x} bytes)", ptr.0);
+    Ok(())
+}
+
+/// How deep boxes may be nested inside each other before reading is aborted.
+pub const MAX_BOX_DEPTH: usize = 64;
+
+/// State shared by every copy of an [`ElfReadDomain`] while reading one file.
+///
+/// The domain itself is `Copy` and gets captured by value in nested read closures,
+/// so it only holds a reference to this.
+#[derive(Debug, Default)]
+pub struct ReadState {
+    /// Offsets of the boxes that are currently being read, innermost last
+    active_boxes: RefCell<Vec<u32>>,
+}
+
+/// Marks the box at an offset as being read until it is dropped, see [`ElfReadDomain::follow_box`].
+struct ActiveBoxGuard<'a>(&'a ReadState);
+
+impl Drop for ActiveBoxGuard<'_> {
+    fn drop(&mut self) {
+        self.0.active_boxes.borrow_mut().pop();
+    }
+}
+
+/// Checks that a table with `count` elements of `stride` bytes each matches the size of its symbol,
+/// which would otherwise mean the table overruns into the next symbol or that the struct size is wrong.
+///
+/// Only warns about a mismatch, so that files with padding after a table can still be read.
+pub fn check_count_matches_symbol(count: u32, symbol: &Symbol, stride: u32) -> bool {
+    let symbol_count = symbol.size() / stride;
+    
+    if symbol_count != count {
+        warn!("Table {} has a count of {count}, but its size of 0x{:x} bytes implies {symbol_count} \
+            elements of 0x{stride:x} bytes", symbol.name, symbol.size());
+    }
+    symbol_count == count
+}
+
 #[derive(Clone, Copy)]
 pub struct ElfReadDomain<'a> {
-    rodata_section: &'a [u8],
+    /// Section the data is read from, which `relocations` belong to
+    content_section: &'a [u8],
+    /// Separate section containing the strings, for files whose data lives in `.data`
+    rodata_section: Option<&'a [u8]>,
     relocations: &'a IndexMap<Pointer, Relocation>,
-    symbols: &'a IndexMap<String, Symbol>,
+    symbols: &'a SymbolTable,
+    state: &'a ReadState,
 }
 
 impl<'a> ElfReadDomain<'a> {
     pub fn new(
-        rodata_section: &'a [u8],
+        content_section: &'a [u8],
         relocations: &'a IndexMap<Pointer, Relocation>,
-        symbols: &'a IndexMap<String, Symbol>,
+        symbols: &'a SymbolTable,
+        state: &'a ReadState,
     ) -> Self {
         Self {
-            rodata_section,
+            content_section,
+            rodata_section: None,
             relocations,
             symbols,
+            state,
         }
     }
     
+    /// Reads strings from `rodata_section` instead of the content section.
+    pub fn with_rodata_section(mut self, rodata_section: &'a [u8]) -> Self {
+        self.rodata_section = Some(rodata_section);
+        self
+    }
+    
+    /// Section that string pointers point into.
+    pub fn string_section(&self) -> &'a [u8] {
+        self.rodata_section.unwrap_or(self.content_section)
+    }
+    
     pub fn find_symbol(&self, name: &str) -> Result<Symbol> {
         let result = self.symbols.get(name)
-            .ok_or_else(|| anyhow!("Could not find symbol {name:?}"))?;
+            .ok_or_else(|| PaintelfError::SymbolNotFound {
+                name: name.to_string(),
+                suggestions: self.similar_symbol_names(name, 3),
+            })?;
         
         Ok(result.clone())
     }
     
+    /// Names of the `count` symbols closest to `name` by edit distance, closest first.
+    pub fn similar_symbol_names(&self, name: &str, count: usize) -> Vec<String> {
+        let mut candidates: Vec<(usize, &str)> = self.symbols.iter()
+            .filter(|symbol| !symbol.name.is_empty())
+            .map(|symbol| (edit_distance(name, &symbol.name), symbol.name.as_str()))
+            .collect();
+        candidates.sort();
+        
+        candidates.into_iter()
+            .take(count)
+            .map(|(_, name)| name.to_string())
+            .collect()
+    }
+    
     // TODO: find a way to do this with less repetition
     pub fn read_string(&self, reader: &mut impl Reader) -> Result<String> {
         let offset = Pointer::current(reader)?;
@@ -53,7 +144,7 @@ impl<'a> ElfReadDomain<'a> {
             bail!("Expected non-nullable string, got null (at offset 0x{:x})", offset.0);
         };
         
-        let result = read_string(self.rodata_section, pointer.0)?;
+        let result = read_string(self.string_section(), pointer.0)?;
         Ok(result.to_string())
     }
     
@@ -61,13 +152,39 @@ impl<'a> ElfReadDomain<'a> {
         let pointer = self.read_pointer_optional(reader)?;
         
         if let Some(pointer) = pointer {
-            let result = read_string(self.rodata_section, pointer.0)?;
+            let result = read_string(self.string_section(), pointer.0)?;
             Ok(Some(result.to_string()))
         } else {
             Ok(None)
         }
     }
     
+    /// Reads `N` elements stored inline at the current position, without a pointer or count.
+    pub fn read_fixed_array<T, const N: usize, R: Reader>(
+        self, reader: &mut R,
+        read_content: impl Fn(&mut R) -> Result<T>,
+    ) -> Result<[T; N]> {
+        let values: Vec<T> = (0..N)
+            .map(|_| read_content(reader))
+            .collect::<Result<_>>()?;
+        
+        values.try_into()
+            .map_err(|values: Vec<T>| anyhow!("Expected {N} elements, read {}", values.len()))
+    }
+    
+    /// Reads the pointers to strings starting at `ptr` until a null pointer is reached.
+    pub fn read_null_terminated_string_array<R: Reader>(self, reader: &mut R, ptr: Pointer) -> Result<Vec<String>> {
+        scoped_reader_pos!(reader);
+        reader.seek(SeekFrom::Start(ptr.into()))?;
+        
+        let mut strings = Vec::new();
+        while let Some(string) = self.read_string_optional(reader)? {
+            strings.push(string);
+        }
+        
+        Ok(strings)
+    }
+    
     pub fn read_vec<T: 'static, R: Reader>(self, reader: &mut R, read_content: impl Fn(&mut R) -> Result<T>) -> Result<Vec<T>> {
         let ptr: Option<Pointer> = self.read_pointer_optional(reader)?;
         let count: u32 = u32::from_reader(reader, self)?;
@@ -80,6 +197,8 @@ impl<'a> ElfReadDomain<'a> {
             return Ok(Vec::new());
         }
         
+        ensure_vec_fits(reader, ptr, count)?;
+        
         scoped_reader_pos!(reader);
         reader.seek(SeekFrom::Start(ptr.into()))?;
         
@@ -90,6 +209,96 @@ impl<'a> ElfReadDomain<'a> {
         Ok(values)
     }
     
+    /// Reads a pointer and count to an array of pointers and follows each of them to read
+    /// one element using `read_content`.
+    ///
+    /// Some tables include their trailing null pointer in the count, which gets skipped
+    /// when `count_includes_null` is set.
+    pub fn read_boxed_vec<T, R: Reader>(
+        self, reader: &mut R, count_includes_null: bool,
+        read_content: impl Fn(&mut R) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let ptr: Option<Pointer> = self.read_pointer_optional(reader)?;
+        let count: u32 = u32::from_reader(reader, self)?;
+        
+        let Some(ptr) = ptr else {
+            return Ok(Vec::new());
+        };
+        
+        let count = if count_includes_null { count.saturating_sub(1) } else { count };
+        
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        
+        ensure_vec_fits(reader, ptr, count)?;
+        
+        scoped_reader_pos!(reader);
+        reader.seek(SeekFrom::Start(ptr.into()))?;
+        
+        self.read_boxed_array(reader, count, read_content)
+    }
+    
+    /// Reads `count` pointers starting at the current position and follows each of them
+    /// to read one element using `read_content`.
+    pub fn read_boxed_array<T, R: Reader>(
+        self, reader: &mut R, count: u32,
+        read_content: impl Fn(&mut R) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        (0..count)
+            .map(|_| self.read_boxed(reader, &read_content))
+            .collect()
+    }
+    
+    /// Reads a pointer and follows it to read one element using `read_content`.
+    pub fn read_boxed<T, R: Reader>(self, reader: &mut R, read_content: impl FnOnce(&mut R) -> Result<T>) -> Result<T> {
+        let ptr = self.read_pointer(reader)?;
+        self.follow_box(reader, ptr, read_content)
+    }
+    
+    /// Reads the content of the box at `ptr`, restoring the reader position afterwards.
+    ///
+    /// Corrupt files could contain boxes that (indirectly) point back to themselves, which would
+    /// otherwise recurse until the stack overflows, so this errors instead when the box at `ptr`
+    /// is already being read or when boxes are nested deeper than [`MAX_BOX_DEPTH`].
+    fn follow_box<T, R: Reader>(self, reader: &mut R, ptr: Pointer, read_content: impl FnOnce(&mut R) -> Result<T>) -> Result<T> {
+        let _guard = {
+            let mut boxes = self.state.active_boxes.borrow_mut();
+            ensure!(!boxes.contains(&ptr.0), "Pointer cycle: box at 0x{:x} (indirectly) contains a pointer to itself", ptr.0);
+            ensure!(boxes.len() < MAX_BOX_DEPTH, "Boxes are nested more than {MAX_BOX_DEPTH} levels deep (at 0x{:x})", ptr.0);
+            
+            boxes.push(ptr.0);
+            ActiveBoxGuard(self.state)
+        };
+        
+        scoped_reader_pos!(reader);
+        reader.seek(SeekFrom::Start(ptr.into()))?;
+        read_content(reader)
+    }
+    
+    /// Reads the table at `data_symbol` whose element count is stored at `count_symbol`,
+    /// calling `read_element` once per element.
+    ///
+    /// Tables ending in a null entry include it in their count, which gets skipped
+    /// when `trailing_null` is set.
+    pub fn read_counted_table<T, R: Reader>(
+        self, reader: &mut R, count_symbol: &str, data_symbol: &str, trailing_null: bool,
+        read_element: impl Fn(&mut R) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let count_symbol = self.find_symbol(count_symbol)?;
+        reader.seek(SeekFrom::Start(count_symbol.offset().into()))?;
+        let count = reader.read_u32::<BigEndian>()?;
+        let count = if trailing_null { count.saturating_sub(1) } else { count };
+        
+        let data_symbol = self.find_symbol(data_symbol)?;
+        ensure_vec_fits(reader, Pointer(data_symbol.offset()), count)?;
+        reader.seek(SeekFrom::Start(data_symbol.offset().into()))?;
+        
+        (0..count)
+            .map(|_| read_element(reader))
+            .collect()
+    }
+    
     pub fn read_pointer(&self, reader: &mut impl Reader) -> Result<Pointer> {
         let offset = Pointer::current(reader)?;
         let optional_pointer = self.read_pointer_optional(reader)?;
@@ -102,21 +311,50 @@ impl<'a> ElfReadDomain<'a> {
     }
     
     pub fn read_pointer_optional(&self, reader: &mut impl Reader) -> Result<Option<Pointer>> {
+        let target = self.read_relocation_target(reader)?;
+        target.map(|(symbol, addend)| symbol_offset_with_addend(symbol, addend)).transpose()
+    }
+    
+    /// Reads a pointer, naming the symbol it points to if it points to the start of a global symbol.
+    pub fn read_symbol_pointer(&self, reader: &mut impl Reader) -> Result<SymbolPointer> {
         let offset = Pointer::current(reader)?;
         
-        let real_value = reader.read_u32::<BigEndian>()?;
-        ensure!(real_value == 0, "Expected pointer, got 0x{real_value:x} (at offset 0x{:x})", offset.0);
+        let Some((symbol, addend)) = self.read_relocation_target(reader)? else {
+            bail!("Expected pointer, got nothing (at offset 0x{:x})", offset.0);
+        };
         
-        if let Some(relocation) = self.relocations.get(&offset) {
-            let symbol = self.symbols.get_index((relocation.info >> 8) as usize)
-                .ok_or_else(|| anyhow!("Could not find symbol at index {}", relocation.info >> 8))?
-                .1;
-            
-            Ok(Some(symbol.offset().into()))
+        if addend == 0 && symbol.header.st_info >> 4 == STB_GLOBAL && !symbol.name.is_empty() {
+            Ok(SymbolPointer::Symbol(symbol.name.clone()))
         } else {
-            Ok(None)
+            Ok(SymbolPointer::Offset(symbol_offset_with_addend(symbol, addend)?))
         }
     }
+    
+    /// Reads a null pointer and returns the symbol and addend of the relocation applied to it, if any.
+    fn read_relocation_target(&self, reader: &mut impl Reader) -> Result<Option<(&'a Symbol, u32)>> {
+        let offset = Pointer::current(reader)?;
+        
+        let real_value = reader.read_u32::<BigEndian>()?;
+        ensure!(real_value == 0, "Expected pointer, got 0x{real_value:x} (at offset 0x{:x})", offset.0);
+        
+        let Some(relocation) = self.relocations.get(&offset) else {
+            return Ok(None);
+        };
+        
+        ensure!(relocation.reloc_type() == R_PPC_ADDR32, "Unsupported relocation type {} at 0x{:x}",
+            relocation.reloc_type(), offset.0);
+        
+        let symbol = self.symbols.get_index(relocation.symbol_index() as usize)
+            .ok_or_else(|| anyhow!("Could not find symbol at index {}", relocation.symbol_index()))?;
+        
+        Ok(Some((symbol, relocation.addend)))
+    }
+}
+
+fn symbol_offset_with_addend(symbol: &Symbol, addend: u32) -> Result<Pointer> {
+    let offset = symbol.offset().checked_add(addend)
+        .ok_or_else(|| anyhow!("Relocation to symbol {} with addend 0x{addend:x} overflows", symbol.name))?;
+    Ok(Pointer(offset))
 }
 
 impl EndianSpecific for ElfReadDomain<'_> {
@@ -133,10 +371,7 @@ impl ReadDomain for ElfReadDomain<'_> {
             return Ok(None);
         };
         
-        scoped_reader_pos!(reader);
-        reader.set_position(ptr)?;
-        
-        read_content(reader).map(Some)
+        self.follow_box(reader, ptr, read_content).map(Some)
     }
 }
 
@@ -158,6 +393,24 @@ impl CanRead<Option<Pointer>> for ElfReadDomain<'_> {
     }
 }
 
+impl CanRead<SymbolPointer> for ElfReadDomain<'_> {
+    fn read(self, reader: &mut impl Reader) -> Result<SymbolPointer> {
+        self.read_symbol_pointer(reader)
+    }
+}
+
+impl CanRead<u64> for ElfReadDomain<'_> {
+    fn read(self, reader: &mut impl Reader) -> Result<u64> {
+        Ok(reader.read_u64::<BigEndian>()?)
+    }
+}
+
+impl CanRead<f64> for ElfReadDomain<'_> {
+    fn read(self, reader: &mut impl Reader) -> Result<f64> {
+        Ok(reader.read_f64::<BigEndian>()?)
+    }
+}
+
 impl CanRead<String> for ElfReadDomain<'_> {
     fn read(self, reader: &mut impl Reader) -> Result<String> {
         self.read_string(reader)
@@ -171,10 +424,13 @@ impl CanRead<Option<String>> for ElfReadDomain<'_> {
 }
 
 // serializing
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum DataCategory {
     Data,
     Rodata,
+    /// Strings that are placed in front of everything else in `.rodata`,
+    /// see [`ElfWriteDomain::string_category`]
+    Strings,
 }
 
 impl HeapCategory for DataCategory {}
@@ -185,6 +441,16 @@ pub enum ElfCategoryType {
     Data,
 }
 
+impl ElfCategoryType {
+    /// Category of the heap that top level data gets written into.
+    pub fn main_category(self) -> DataCategory {
+        match self {
+            ElfCategoryType::Unit => DataCategory::Rodata,
+            ElfCategoryType::Data => DataCategory::Data,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WriteStringArgs {
     pub deduplicate: bool,
@@ -210,6 +476,8 @@ impl Default for NewWriteStringArgs {
 #[derive(Debug, Clone, Default)]
 pub struct WriteSliceArgs {
     pub symbol_name: Option<SymbolName>,
+    /// Write the element count before the pointer (`{count, ptr}`) instead of after it
+    pub count_first: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -224,13 +492,49 @@ pub struct NewWriteNullTermiantedSliceArgs {
     pub write_length: bool,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct WriteSymbolArgs {
+    /// Aligns the position to this many bytes after the content of the symbol was written,
+    /// for reproducing padding between symbols. The padding does not count towards the symbol size.
+    pub trailing_alignment: Option<usize>,
+}
+
 #[derive(Clone)]
 pub struct ElfWriteDomain {
-    pub string_map: HashMap<String, HeapToken>,
+    /// Already written strings, together with the heap of the symbol declared for them
+    pub string_map: HashMap<String, (HeapToken, Option<DataCategory>)>,
     pub symbol_declarations: Vec<SymbolDeclaration>,
     pub relocations: Vec<RelDeclaration>,
     pub string_dedup_size: u64,
     pub apply_debug_relocations: bool,
+    /// Master switch for string deduplication, which overrides [`WriteStringArgs::deduplicate`]
+    /// when turned off. Needs to be on for the output to match the original files.
+    pub deduplicate_strings: bool,
+    
+    /// Initial character of the auto-generated names of boxed values
+    pub box_symbol_char: char,
+    /// Alignment (`sh_addralign`) the content sections need, which is raised
+    /// to 8 as soon as any 8 byte values are written
+    pub content_alignment: u32,
+    /// Heap that [`ElfWriteDomain::write_string`] puts new strings into. Either interleaved with
+    /// the rest of `.rodata` ([`DataCategory::Rodata`], the default) or in a block of their own
+    /// at the start of it ([`DataCategory::Strings`]).
+    pub string_category: DataCategory,
+    
+    /// Amount of strings written into the heap
+    pub strings_written: usize,
+    /// Amount of strings that reused an already written string
+    pub strings_deduplicated: usize,
+    /// Amount of strings that were written again because they came after `string_dedup_size`,
+    /// even though the same string had already been written before
+    pub strings_past_dedup_size: usize,
+    
+    /// Pointers to symbols referenced by name, which get turned into relocations
+    /// once the location of every symbol is known
+    pub symbol_references: Vec<(HeapToken, String)>,
+    /// Locations of pointers to symbols declared with a category of their own, since their
+    /// relocations need to know which section they point into, see [`RelDeclaration::target_category`]
+    pub pointer_categories: Vec<(HeapToken, DataCategory)>,
     
     prev_string_len: usize,
 }
@@ -249,10 +553,37 @@ impl ElfWriteDomain {
             relocations: Vec::new(),
             string_dedup_size,
             apply_debug_relocations,
+            deduplicate_strings: true,
+            box_symbol_char: BOX_SYMBOL_PREFIX,
+            content_alignment: 4,
+            string_category: DataCategory::Rodata,
+            strings_written: 0,
+            strings_deduplicated: 0,
+            strings_past_dedup_size: 0,
+            symbol_references: Vec::new(),
+            pointer_categories: Vec::new(),
             prev_string_len: 0,
         }
     }
     
+    /// Looks up an already written copy of `value` if strings at `position` get deduplicated.
+    fn find_written_string(&mut self, position: u64, value: &str, deduplicate: bool) -> Option<(HeapToken, Option<DataCategory>)> {
+        if !deduplicate || !self.deduplicate_strings {
+            return None;
+        }
+        
+        let existing_token = self.string_map.get(value).copied();
+        
+        if position >= self.string_dedup_size {
+            if existing_token.is_some() {
+                self.strings_past_dedup_size += 1;
+            }
+            return None;
+        }
+        
+        existing_token
+    }
+    
     pub fn write_string_optional(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: Option<&str>, args: WriteStringArgs) -> Result<()> {
         let Some(value) = value else {
             0u32.to_writer(ctx, self)?;
@@ -265,13 +596,13 @@ impl ElfWriteDomain {
     pub fn write_string(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &str, args: WriteStringArgs) -> Result<()> {
         // Search for if this string has already been written before
         // TODO: account for substrings (use crate memchr?)
-        let existing_token = if args.deduplicate && ctx.position()? < self.string_dedup_size { 
-            self.string_map.get(value).copied()
-        } else {
-            None
-        };
+        let existing_token = self.find_written_string(ctx.position()?, value, args.deduplicate);
         
-        if let Some(token) = existing_token {
+        if let Some((token, category)) = existing_token {
+            self.strings_deduplicated += 1;
+            if let Some(category) = category {
+                self.pointer_categories.push((ctx.heap_token_at_current_pos()?, category));
+            }
             ctx.write_token::<4>(token)?;
             return Ok(());
         }
@@ -283,7 +614,7 @@ impl ElfWriteDomain {
         }
         
         let mut name_size: usize = 0;
-        let new_token = ctx.allocate_next_block_aligned(Some(DataCategory::Rodata), alignment, |ctx| {
+        let new_token = ctx.allocate_next_block_aligned(Some(self.string_category), alignment, |ctx| {
             let start_pos = ctx.position()? as usize;
             ctx.write_c_str(value)?;
             if value.len() > 2 {
@@ -293,16 +624,19 @@ impl ElfWriteDomain {
             Ok(())
         })?;
         
+        self.strings_written += 1;
         self.put_symbol(SymbolDeclaration {
-            name: SymbolName::Internal('.'),
+            name: SymbolName::Internal(STRING_SYMBOL_PREFIX),
             offset: new_token,
             size: name_size as u32,
+            category: Some(DataCategory::Rodata),
         });
         
         if args.deduplicate {
-            self.string_map.insert(value.to_string(), new_token);
+            self.string_map.insert(value.to_string(), (new_token, Some(DataCategory::Rodata)));
         }
         
+        self.pointer_categories.push((ctx.heap_token_at_current_pos()?, DataCategory::Rodata));
         ctx.write_token::<4>(new_token)?;
         Ok(())
     }
@@ -322,13 +656,13 @@ impl ElfWriteDomain {
     }
     
     pub fn write_string_new_post(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &str, args: NewWriteStringArgs, base: HeapToken) -> Result<()> {
-        let existing_token = if args.deduplicate && ctx.position()? < self.string_dedup_size { 
-            self.string_map.get(value).copied()
-        } else {
-            None
-        };
+        let existing_token = self.find_written_string(ctx.position()?, value, args.deduplicate);
         
-        if let Some(token) = existing_token {
+        if let Some((token, category)) = existing_token {
+            self.strings_deduplicated += 1;
+            if let Some(category) = category {
+                self.pointer_categories.push((base, category));
+            }
             ctx.add_relocation(base, token)?;
             return Ok(());
         }
@@ -350,14 +684,16 @@ impl ElfWriteDomain {
         }
         let name_size = ctx.position()? as usize - start_pos;
         
+        self.strings_written += 1;
         self.put_symbol(SymbolDeclaration {
-            name: SymbolName::Internal('.'),
+            name: SymbolName::Internal(STRING_SYMBOL_PREFIX),
             offset: new_token,
             size: name_size as u32,
+            category: None,
         });
         
         if args.deduplicate {
-            self.string_map.insert(value.to_string(), new_token);
+            self.string_map.insert(value.to_string(), (new_token, None));
         }
         Ok(())
     }
@@ -381,11 +717,23 @@ impl ElfWriteDomain {
                 name,
                 offset: token,
                 size: links_size as u32,
+                category: None,
             });
         }
         Ok(())
     }
     
+    /// Writes the elements of `values` inline at the current position, without a pointer or count.
+    pub fn write_fixed_array<T, const N: usize, W: WriteCtx<DataCategory>>(
+        &mut self, ctx: &mut W, values: &[T; N],
+        write_content: impl Fn(&mut Self, &mut W, &T) -> Result<()>,
+    ) -> Result<()> {
+        for value in values {
+            write_content(self, ctx, value)?;
+        }
+        Ok(())
+    }
+    
     pub fn write_slice<T: 'static, W: WriteCtx<DataCategory>>(
         &mut self, ctx: &mut W, values: &[T], args: WriteSliceArgs,
         write_content: impl Fn(&mut Self, &mut W::InnerCtx<'_>, &T) -> Result<()>,
@@ -400,14 +748,20 @@ impl ElfWriteDomain {
             Ok(())
         })?;
         
-        ctx.write_token::<4>(token)?;
-        (values.len() as u32).to_writer(ctx, self)?;
+        if args.count_first {
+            (values.len() as u32).to_writer(ctx, self)?;
+            ctx.write_token::<4>(token)?;
+        } else {
+            ctx.write_token::<4>(token)?;
+            (values.len() as u32).to_writer(ctx, self)?;
+        }
         
         if let Some(name) = args.symbol_name {
             self.put_symbol(SymbolDeclaration {
                 name,
                 offset: token,
                 size: links_size as u32,
+                category: None,
             });
         }
         Ok(())
@@ -438,6 +792,7 @@ impl ElfWriteDomain {
                 name,
                 offset: token,
                 size: links_size as u32,
+                category: None,
             });
         }
         Ok(())
@@ -494,6 +849,7 @@ impl ElfWriteDomain {
                 name,
                 offset: new_token,
                 size: links_size as u32,
+                category: None,
             });
         }
         Ok(())
@@ -504,6 +860,16 @@ impl ElfWriteDomain {
         ctx: &mut W,
         symbol_name: impl Into<String>,
         content_callback: impl FnOnce(&mut Self, &mut W) -> Result<()>
+    ) -> Result<()> {
+        self.write_symbol_with_args(ctx, symbol_name, WriteSymbolArgs::default(), content_callback)
+    }
+    
+    pub fn write_symbol_with_args<W: WriteCtx<DataCategory>>(
+        &mut self,
+        ctx: &mut W,
+        symbol_name: impl Into<String>,
+        args: WriteSymbolArgs,
+        content_callback: impl FnOnce(&mut Self, &mut W) -> Result<()>
     ) -> Result<()> {
         let token = ctx.heap_token_at_current_pos()?;
         let start_offset = ctx.position()?;
@@ -512,14 +878,42 @@ impl ElfWriteDomain {
         
         let size = ctx.position()? - start_offset;
         
+        if let Some(alignment) = args.trailing_alignment {
+            ctx.align_to(alignment)?;
+        }
+        
         self.put_symbol(SymbolDeclaration {
             name: SymbolName::Unmangled(symbol_name.into()),
             offset: token,
             size: size as u32,
+            category: None,
         });
         Ok(())
     }
     
+    /// Writes `values` followed by a null word as the symbol `table_symbol`, and the amount of entries
+    /// including that null terminator as the symbol `count_symbol`, which is how tables are stored
+    /// in dispos, chr and lct.
+    pub fn write_null_terminated_counted_table<W: WriteCtx<DataCategory>, T>(
+        &mut self,
+        ctx: &mut W,
+        count_symbol: &str,
+        table_symbol: &str,
+        values: &[T],
+        mut write_value: impl FnMut(&mut Self, &mut W, &T) -> Result<()>,
+    ) -> Result<()> {
+        self.write_symbol(ctx, count_symbol, |domain, ctx| {
+            (values.len() as u32 + 1).to_writer(ctx, domain)
+        })?;
+        
+        self.write_symbol(ctx, table_symbol, |domain, ctx| {
+            for value in values {
+                write_value(domain, ctx, value)?;
+            }
+            0u32.to_writer(ctx, domain)
+        })
+    }
+    
     pub fn put_symbol(&mut self, symbol: SymbolDeclaration) {
         self.symbol_declarations.push(symbol);
     }
@@ -529,7 +923,7 @@ impl ElfWriteDomain {
     }
     
     pub fn write_pointer_debug(&mut self, writer: &mut impl Writer, value: Pointer) -> Result<()> {
-        writer.write_u32::<BigEndian>(value.0 | 0x70000000)?;
+        writer.write_u32::<BigEndian>(value.0 | DEBUG_RELOCATION_TAG)?;
         Ok(())
     }
 }
@@ -542,6 +936,8 @@ impl WriteDomain for ElfWriteDomain {
         self.put_relocation(RelDeclaration {
             base_location: writer.position()? as usize,
             target_location: heap_offset,
+            addend: 0,
+            target_category: None,
         });
         
         if self.apply_debug_relocations {
@@ -557,8 +953,7 @@ impl CanWriteBox<DataCategory> for ElfWriteDomain {
         ctx: &mut W,
         write_content: impl FnOnce(&mut Self, &mut W::InnerCtx<'_>) -> Result<()>
     ) -> Result<()> {
-        // hardcoding 'l' to make lct work is quite a hack
-        self.write_box(ctx, Some(SymbolName::Internal('l')), write_content)
+        self.write_box(ctx, Some(SymbolName::Internal(self.box_symbol_char)), write_content)
     }
 }
 
@@ -650,6 +1045,42 @@ impl<T: Default + 'static> CanWriteSliceWithArgs<DataCategory, T, NewWriteNullTe
     }
 }
 
+impl CanWrite<DataCategory, Pointer> for ElfWriteDomain {
+    fn write(&mut self, _: &mut impl WriteCtx<DataCategory>, value: &Pointer) -> Result<()> {
+        // raw pointers would need to be turned into a relocation to whatever they point at,
+        // which is not known when the target is not part of the deserialized data
+        bail!("Cannot write raw pointer {value:?}, the data it points to is not supported yet")
+    }
+}
+
+impl CanWrite<DataCategory, SymbolPointer> for ElfWriteDomain {
+    fn write(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &SymbolPointer) -> Result<()> {
+        match value {
+            SymbolPointer::Symbol(name) => {
+                self.symbol_references.push((ctx.heap_token_at_current_pos()?, name.clone()));
+                0u32.to_writer(ctx, self)
+            },
+            SymbolPointer::Offset(pointer) => self.write(ctx, pointer),
+        }
+    }
+}
+
+impl CanWrite<DataCategory, u64> for ElfWriteDomain {
+    fn write(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &u64) -> Result<()> {
+        self.content_alignment = self.content_alignment.max(8);
+        
+        // big endian, so the high half comes first
+        ((*value >> 32) as u32).to_writer(ctx, self)?;
+        (*value as u32).to_writer(ctx, self)
+    }
+}
+
+impl CanWrite<DataCategory, f64> for ElfWriteDomain {
+    fn write(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &f64) -> Result<()> {
+        self.write(ctx, &value.to_bits())
+    }
+}
+
 impl CanWrite<DataCategory, String> for ElfWriteDomain {
     fn write(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &String) -> Result<()> {
         self.write_string(ctx, value, WriteStringArgs::default())
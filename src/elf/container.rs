@@ -1,28 +1,45 @@
-use core::mem::{self, offset_of};
+use core::{
+    fmt::{self, Debug},
+    mem::{self, offset_of},
+};
 use std::io::{Cursor, SeekFrom, Write};
 
-use anyhow::{Error, Result, anyhow, bail};
+use anyhow::{Error, Result, anyhow, bail, ensure};
 use binrw::{BinRead, BinWrite};
 use indexmap::IndexMap;
+use log::warn;
 use memchr::memmem;
 use vivibin::{Reader, Writer, align_to, util::HashMap};
 
 use crate::{
     elf::{
         Relocation, SHF_ALLOC, SHF_INFO_LINK, Section, SectionHeader, SectionType, Symbol,
-        SymbolHeader,
+        SymbolHeader, SymbolTable, read_bytes_at,
     },
+    formats::{FileData, FileType},
+    link_section_debug, read_file_data,
     util::{pointer::Pointer, read_string},
 };
 
-pub const ELF_HEADER_IDENT: [u8; 12] = [0x7F, 0x45, 0x4C, 0x46, 0x01, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00];
+/// `e_ident` of a 32-bit big endian ELF file. The last padding byte differs between some of the game's files,
+/// see [`ElfHeader::ident_padding_byte`].
+pub const ELF_HEADER_IDENT: [u8; 16] = [
+    0x7F, 0x45, 0x4C, 0x46, 0x01, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// `e_type` of relocatable files, which all of the game's data files are.
+pub const ET_REL: u16 = 1;
+/// `e_machine` of PowerPC files.
+pub const EM_PPC: u16 = 0x14;
+/// `e_flags` of the game's files, marking them as using the embedded PowerPC ABI.
+pub const EF_PPC_EMB: u32 = 0x8000_0000;
 
 #[derive(Debug, Clone, BinRead, BinWrite)]
 #[brw(big)]
 #[repr(C)]
 pub struct ElfHeader {
-    pub e_ident: [u8; 12],
-    pub e_ident_padding_unk: u32,
+    /// Full ELF identification, including the padding after `EI_ABIVERSION`
+    pub e_ident: [u8; 16],
     pub e_type: u16,
     pub e_machine: u16,
     pub e_version: u32,
@@ -41,12 +58,44 @@ pub struct ElfHeader {
     pub e_shstrndx: u16,
 }
 
-#[derive(Debug)]
+impl ElfHeader {
+    /// Last byte of the `e_ident` padding, which is 1 in data_fld_maplink.elf and 0 in all other known files.
+    pub fn ident_padding_byte(&self) -> u8 {
+        self.e_ident[15]
+    }
+}
+
 pub struct ElfContainer {
     pub header: ElfHeader,
-    pub symbols: IndexMap<String, Symbol>,
+    pub symbols: SymbolTable,
     pub content_sections: IndexMap<String, Section>,
     pub meta_sections: IndexMap<String, Section>,
+    /// Names of all sections in the order of the section header table of the file this was read from,
+    /// which is what `st_shndx` of the symbols refers to. Empty for containers built in memory.
+    pub original_section_order: Vec<String>,
+}
+
+// the full symbol table and section contents are way too big to be printed
+impl Debug for ElfContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct SectionList<'a>(&'a IndexMap<String, Section>);
+        
+        impl Debug for SectionList<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_list()
+                    .entries(self.0.values()
+                        .filter(|section| !section.name.is_empty())
+                        .map(|section| format_args!("{} <{} bytes>", section.name, section.content.len())))
+                    .finish()
+            }
+        }
+        
+        f.debug_struct("ElfContainer")
+            .field("symbols", &format_args!("<{}>", self.symbols.len()))
+            .field("content_sections", &SectionList(&self.content_sections))
+            .field("meta_sections", &SectionList(&self.meta_sections))
+            .finish()
+    }
 }
 
 impl ElfContainer {
@@ -56,9 +105,10 @@ impl ElfContainer {
         
         Self {
             header,
-            symbols: IndexMap::new(),
+            symbols: SymbolTable::new(),
             content_sections,
             meta_sections: IndexMap::new(),
+            original_section_order: Vec::new(),
         }
     }
     
@@ -72,6 +122,454 @@ impl ElfContainer {
         }
     }
     
+    /// Like [`ElfContainer::get_section`], but the error lists the sections that do exist.
+    pub fn require_section(&self, name: &str) -> Result<&Section> {
+        self.get_section(name).ok_or_else(|| self.missing_section_error(name))
+    }
+    
+    /// Error for a section `name` that could not be found, listing all sections that do exist.
+    pub fn missing_section_error(&self, name: &str) -> anyhow::Error {
+        anyhow!("Could not find section {name}; available: {}", self.section_names().join(", "))
+    }
+    
+    /// Reads the content of this file as `file_type`, looking up the right content section
+    /// and its relocations, see [`crate::read_file_data`].
+    pub fn read_file_data(&self, file_type: FileType) -> Result<FileData> {
+        read_file_data(self, file_type)
+    }
+    
+    /// Every null terminated string in section `section_name` together with its offset,
+    /// e.g. all symbol names in `.strtab` or the text of formats keeping their data in `.data`.
+    ///
+    /// Empty strings and byte sequences that aren't valid UTF-8 are skipped.
+    pub fn strings(&self, section_name: &str) -> Result<Vec<(u32, &str)>> {
+        let content = &self.require_section(section_name)?.content;
+        let mut strings = Vec::new();
+        let mut offset = 0;
+        
+        while let Some(len) = memchr::memchr(0, &content[offset..]) {
+            if let Ok(string) = str::from_utf8(&content[offset..offset + len]) && !string.is_empty() {
+                strings.push((offset as u32, string));
+            }
+            
+            offset += len + 1;
+        }
+        
+        Ok(strings)
+    }
+    
+    /// Names of all sections except for the null section, content sections first.
+    pub fn section_names(&self) -> Vec<&str> {
+        self.content_sections.keys()
+            .chain(self.meta_sections.keys())
+            .filter(|name| !name.is_empty())
+            .map(String::as_str)
+            .collect()
+    }
+    
+    /// Returns the names of all sections in the order they appear in the section header table
+    /// written by [`ElfContainer::to_bytes`].
+    pub fn section_header_order(&self) -> Vec<&str> {
+        let mut names = vec![""];
+        
+        for section in self.content_sections.values() {
+            if section.name.is_empty() {
+                continue;
+            }
+            
+            names.push(&section.name);
+            
+            let rela_name = format!(".rela{}", section.name);
+            if let Some((rela_name, _)) = self.meta_sections.get_key_value(rela_name.as_str()) {
+                names.push(rela_name);
+            }
+        }
+        
+        names.extend([".shstrtab", ".symtab", ".strtab"]);
+        names
+    }
+    
+    /// Names of all sections in the order that `st_shndx` of the symbols refers to, which is
+    /// [`ElfContainer::original_section_order`] for files that were read and
+    /// [`ElfContainer::section_header_order`] for containers built in memory.
+    fn symbol_section_order(&self) -> Vec<&str> {
+        if self.original_section_order.is_empty() {
+            self.section_header_order()
+        } else {
+            self.original_section_order.iter().map(String::as_str).collect()
+        }
+    }
+    
+    /// Name of the section that symbols with `st_shndx` set to `index` are in.
+    pub fn section_name_at(&self, index: u16) -> Option<&str> {
+        self.symbol_section_order().get(index as usize).copied()
+    }
+    
+    /// Index of section `name` as used by `st_shndx` of the symbols, see [`ElfContainer::section_name_at`].
+    pub fn section_index_of(&self, name: &str) -> Option<u16> {
+        self.symbol_section_order().iter()
+            .position(|other| *other == name)
+            .map(|index| index as u16)
+    }
+    
+    /// Returns the name and content size of every section in section header table order,
+    /// without the null section.
+    pub fn section_sizes(&self) -> Vec<(&str, usize)> {
+        self.section_header_order().into_iter()
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| Some((name, self.get_section(name)?.content.len())))
+            .collect()
+    }
+    
+    /// Builds the content of the section header string table from the names of all sections.
+    ///
+    /// Names of content sections with relocations are only stored as the suffix of their
+    /// relocation section's name, like in the original files.
+    pub fn section_name_table(&self) -> Vec<u8> {
+        let mut table = b"\0.symtab\0.strtab\0.shstrtab\0".to_vec();
+        
+        for section in self.content_sections.values() {
+            if section.name.is_empty() {
+                continue;
+            }
+            
+            let rela_name = format!(".rela{}", section.name);
+            let name = if self.meta_sections.contains_key(&rela_name) {
+                &rela_name
+            } else {
+                &section.name
+            };
+            
+            table.extend_from_slice(name.as_bytes());
+            table.push(0);
+        }
+        
+        table
+    }
+    
+    /// Recalculates the section count, the section header string table index and the links
+    /// between sections based on the order they are serialized in.
+    pub fn update_section_links(&mut self) {
+        let order: Vec<String> = self.section_header_order()
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        let index_of = |name: &str| order.iter().position(|other| other == name).unwrap_or(0) as u32;
+        
+        let symtab_index = index_of(".symtab");
+        let strtab_index = index_of(".strtab");
+        
+        for section in self.meta_sections.values_mut() {
+            if let Some(target_name) = section.name.strip_prefix(".rela") {
+                section.header.sh_link = symtab_index;
+                section.header.sh_info = index_of(target_name);
+            } else if section.name == ".symtab" {
+                section.header.sh_link = strtab_index;
+            }
+        }
+        
+        self.header.e_shnum = order.len() as u16;
+        self.header.e_shstrndx = index_of(".shstrtab") as u16;
+    }
+    
+    /// Returns the content of section `name` with every relocated pointer replaced
+    /// by its target offset, see [`link_section_debug`].
+    pub fn section_with_relocations_applied(&self, name: &str) -> Result<Vec<u8>> {
+        link_section_debug(self.require_section(name)?, &self.symbols)
+    }
+    
+    /// Checks that the section links and counts in the headers are consistent with
+    /// the sections in this container, as they would be serialized by [`ElfContainer::to_bytes`].
+    pub fn verify(&self) -> Result<()> {
+        // section_header_order always lists these, so check that they actually exist
+        let symtab = self.meta_sections.get(".symtab").ok_or_else(|| anyhow!("Could not find section .symtab"))?;
+        self.meta_sections.get(".strtab").ok_or_else(|| anyhow!("Could not find section .strtab"))?;
+        self.meta_sections.get(".shstrtab").ok_or_else(|| anyhow!("Could not find section .shstrtab"))?;
+        
+        let order = self.section_header_order();
+        let index_of = |name: &str| order.iter().position(|other| *other == name);
+        
+        ensure!(self.header.e_shnum as usize == order.len(),
+            "Header contains {} sections, but there are {}", self.header.e_shnum, order.len());
+        ensure!(index_of(".shstrtab") == Some(self.header.e_shstrndx as usize),
+            "Header points to section {} as the section header string table", self.header.e_shstrndx);
+        
+        let symtab_index = index_of(".symtab").ok_or_else(|| anyhow!("Could not find section .symtab"))?;
+        let strtab_index = index_of(".strtab").ok_or_else(|| anyhow!("Could not find section .strtab"))?;
+        
+        for section in self.meta_sections.values() {
+            if let Some(target_name) = section.name.strip_prefix(".rela") {
+                let target_index = index_of(target_name)
+                    .ok_or_else(|| anyhow!("Section {} relocates nonexistent section {target_name}", section.name))?;
+                
+                ensure!(section.header.sh_link as usize == symtab_index,
+                    "Section {} links to section {} instead of .symtab ({symtab_index})", section.name, section.header.sh_link);
+                ensure!(section.header.sh_info as usize == target_index,
+                    "Section {} relocates section {} instead of {target_name} ({target_index})", section.name, section.header.sh_info);
+            }
+        }
+        
+        ensure!(symtab.header.sh_link as usize == strtab_index,
+            "Section .symtab links to section {} instead of .strtab ({strtab_index})", symtab.header.sh_link);
+        
+        // all local symbols (binding 0) have to come before the first non-local one
+        let last_local_symbol = symtab.header.sh_info as usize;
+        for (index, symbol) in self.symbols.iter().enumerate() {
+            let is_local = symbol.header.st_info >> 4 == 0;
+            ensure!(is_local == (index < last_local_symbol),
+                "Symbol {index} ({}) is {} but .symtab declares {last_local_symbol} local symbols",
+                symbol.name, if is_local { "local" } else { "not local" });
+        }
+        
+        for section in self.content_sections.values() {
+            let Some(relocations) = &section.relocations else {
+                continue;
+            };
+            
+            for relocation in relocations.values() {
+                ensure!((relocation.symbol_index() as usize) < self.symbols.len(),
+                    "Relocation at offset 0x{:x} in section .rela{} references nonexistent symbol {}",
+                    relocation.offset, section.name, relocation.symbol_index());
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Renames a symbol in place, rewriting its name in `.strtab` and shifting the name offsets
+    /// of all symbols whose names are stored after it. If the name is shared with other symbols,
+    /// e.g. `bar` being stored as the tail of `foobar`, the new name is appended to `.strtab` instead.
+    ///
+    /// Symbol indices do not change, so relocations stay valid.
+    pub fn rename_symbol(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        ensure!(!new_name.contains('\0'), "Symbol name {new_name:?} cannot contain null bytes");
+        ensure!(!self.symbols.contains(new_name), "Symbol {new_name} already exists");
+        
+        let Some(symbol_index) = self.symbols.index_of(old_name) else {
+            bail!("Could not find symbol {old_name}");
+        };
+        let symbol = self.symbols.get_index(symbol_index).unwrap();
+        ensure!(symbol.header.st_info != 3, "Cannot rename section symbol {old_name}");
+        
+        let name_start = symbol.header.st_name;
+        let name_end = name_start + old_name.len() as u32;
+        
+        // any other name overlapping this one (including the null terminator) would change as well
+        let is_shared = self.symbols.iter().enumerate()
+            .any(|(index, other)| index != symbol_index && other.header.st_info != 3
+                && other.header.st_name <= name_end && name_start <= other.header.st_name + other.name.len() as u32);
+        
+        let strtab_len = self.meta_sections.get(".strtab")
+            .ok_or_else(|| anyhow!("Could not find section .strtab"))?
+            .content.len() as u32;
+        let (new_name_start, delta) = if is_shared {
+            (strtab_len, 0)
+        } else {
+            (name_start, new_name.len() as i64 - old_name.len() as i64)
+        };
+        let update_st_name = |index: usize, st_name: u32| {
+            if index == symbol_index {
+                new_name_start
+            } else if st_name > name_end {
+                (st_name as i64 + delta) as u32
+            } else {
+                st_name
+            }
+        };
+        
+        // read raw symbol table
+        let symtab = self.meta_sections.get_mut(".symtab")
+            .ok_or_else(|| anyhow!("Could not find section .symtab"))?;
+        
+        // the symbol table gets rewritten with entries of the default size below
+        ensure!(symtab.entry_size::<SymbolHeader>() == mem::size_of::<SymbolHeader>(),
+            "Cannot rename symbols in a symbol table with 0x{:x} byte entries", symtab.header.sh_entsize);
+        let symbol_headers: Vec<SymbolHeader> = symtab.read_entries()?;
+        
+        // write new symbol table
+        let mut writer = Cursor::new(Vec::with_capacity(symtab.content.len()));
+        for (index, mut header) in symbol_headers.into_iter().enumerate() {
+            header.st_name = update_st_name(index, header.st_name);
+            header.write(&mut writer)?;
+        }
+        symtab.content = writer.into_inner();
+        
+        // replace name in string table, or leave it to the other symbols if it is shared
+        let strtab = self.meta_sections.get_mut(".strtab")
+            .ok_or_else(|| anyhow!("Could not find section .strtab"))?;
+        if is_shared {
+            strtab.content.extend_from_slice(new_name.as_bytes());
+            strtab.content.push(0);
+        } else {
+            strtab.content.splice(name_start as usize..name_end as usize, new_name.bytes());
+        }
+        
+        // update parsed symbols
+        for (index, header) in self.symbols.headers_mut().enumerate() {
+            header.st_name = update_st_name(index, header.st_name);
+        }
+        self.symbols.rename(symbol_index, new_name.to_owned());
+        
+        Ok(())
+    }
+    
+    /// Replaces every string in `.rodata` that is pointed to by a relocation and has an entry
+    /// in `replacements`, moving all data after it and fixing up symbols and relocations.
+    ///
+    /// To keep the data after a replaced string aligned, the size difference gets rounded
+    /// to a multiple of the section's alignment by adding null bytes.
+    pub fn replace_strings(&mut self, replacements: &HashMap<String, String>) -> Result<()> {
+        const SECTION_NAME: &str = ".rodata";
+        
+        let section_index = self.section_index_of(SECTION_NAME)
+            .ok_or_else(|| self.missing_section_error(SECTION_NAME))?;
+        let section = self.require_section(SECTION_NAME)?;
+        let align = section.header.sh_addralign.max(1) as i64;
+        
+        // offsets in the section that pointers point to
+        let mut targets: Vec<u32> = Vec::new();
+        for section in self.content_sections.values() {
+            for relocation in section.relocations.iter().flat_map(|relocations| relocations.values()) {
+                let symbol = self.symbols.get_index(relocation.symbol_index() as usize)
+                    .ok_or_else(|| anyhow!("Relocation at offset 0x{:x} in section .rela{} references nonexistent symbol {}",
+                        relocation.offset, section.name, relocation.symbol_index()))?;
+                
+                if symbol.header.st_shndx == section_index {
+                    targets.push(symbol.offset().wrapping_add(relocation.addend));
+                }
+            }
+        }
+        targets.sort_unstable();
+        targets.dedup();
+        
+        // (start offset, old size, new content)
+        let mut changes: Vec<(u32, u32, Vec<u8>)> = Vec::new();
+        for &target in &targets {
+            let Ok(string) = read_string(&section.content, target) else {
+                continue;
+            };
+            let Some(replacement) = replacements.get(string) else {
+                continue;
+            };
+            ensure!(!replacement.contains('\0'), "Replacement {replacement:?} cannot contain null bytes");
+            
+            let old_size = string.len() as u32 + 1;
+            let mut bytes = Vec::with_capacity(replacement.len() + align as usize);
+            bytes.extend_from_slice(replacement.as_bytes());
+            bytes.push(0);
+            let padding = (old_size as i64 - bytes.len() as i64).rem_euclid(align);
+            bytes.resize(bytes.len() + padding as usize, 0);
+            
+            changes.push((target, old_size, bytes));
+        }
+        
+        if changes.is_empty() {
+            return Ok(());
+        }
+        
+        // nothing may point into a replaced string except for its start
+        for &(start, old_size, _) in &changes {
+            let inner = start + 1..start + old_size;
+            
+            if let Some(target) = targets.iter().find(|&&target| inner.contains(&target)) {
+                bail!("Cannot replace string at 0x{start:x} because 0x{target:x} points into the middle of it");
+            }
+            if let Some(symbol) = self.symbols.iter().find(|symbol| symbol.header.st_shndx == section_index && inner.contains(&symbol.offset())) {
+                bail!("Cannot replace string at 0x{start:x} because symbol {} starts in the middle of it", symbol.name);
+            }
+            if let Some(relocations) = &section.relocations {
+                ensure!(!relocations.keys().any(|offset| (start..start + old_size).contains(&offset.0)),
+                    "Cannot replace string at 0x{start:x} because it contains a pointer");
+            }
+        }
+        
+        let move_offset = |offset: u32| -> u32 {
+            let delta: i64 = changes.iter()
+                .filter(|(start, old_size, _)| start + old_size <= offset)
+                .map(|(_, old_size, bytes)| bytes.len() as i64 - *old_size as i64)
+                .sum();
+            (offset as i64 + delta) as u32
+        };
+        
+        let mut content = Vec::with_capacity(section.content.len());
+        let mut pos = 0;
+        for (start, old_size, bytes) in &changes {
+            content.extend_from_slice(&section.content[pos..*start as usize]);
+            content.extend_from_slice(bytes);
+            pos = (start + old_size) as usize;
+        }
+        content.extend_from_slice(&section.content[pos..]);
+        
+        // relocations need the old symbol offsets, so update them first
+        let mut new_relocations = Vec::new();
+        for section in self.content_sections.values() {
+            let Some(relocations) = &section.relocations else {
+                continue;
+            };
+            
+            let relocations: IndexMap<Pointer, Relocation> = relocations.values()
+                .map(|relocation| {
+                    let mut relocation = relocation.clone();
+                    let symbol = self.symbols.get_index(relocation.symbol_index() as usize).unwrap();
+                    
+                    if section.name == SECTION_NAME {
+                        relocation.offset = move_offset(relocation.offset);
+                    }
+                    if symbol.header.st_shndx == section_index {
+                        let target = symbol.offset().wrapping_add(relocation.addend);
+                        relocation.addend = move_offset(target).wrapping_sub(move_offset(symbol.offset()));
+                    }
+                    
+                    (Pointer(relocation.offset), relocation)
+                })
+                .collect();
+            
+            new_relocations.push((section.name.clone(), relocations));
+        }
+        
+        let move_symbol = |header: &mut SymbolHeader| {
+            if header.st_shndx == section_index && header.st_info != 3 {
+                let end = header.st_value + header.st_size;
+                header.st_value = move_offset(header.st_value);
+                header.st_size = move_offset(end) - header.st_value;
+            }
+        };
+        
+        // write new symbol table
+        let symtab = self.meta_sections.get_mut(".symtab")
+            .ok_or_else(|| anyhow!("Could not find section .symtab"))?;
+        ensure!(symtab.entry_size::<SymbolHeader>() == mem::size_of::<SymbolHeader>(),
+            "Cannot move symbols in a symbol table with 0x{:x} byte entries", symtab.header.sh_entsize);
+        
+        let mut writer = Cursor::new(Vec::with_capacity(symtab.content.len()));
+        for mut header in symtab.read_entries::<SymbolHeader>()? {
+            move_symbol(&mut header);
+            header.write(&mut writer)?;
+        }
+        symtab.content = writer.into_inner();
+        
+        for header in self.symbols.headers_mut() {
+            move_symbol(header);
+        }
+        
+        // write new relocations
+        for (name, relocations) in new_relocations {
+            let relocation_section_name = format!(".rela{name}");
+            if let Some(relocation_section) = self.meta_sections.get_mut(&relocation_section_name) {
+                let mut writer = Cursor::new(Vec::with_capacity(relocation_section.content.len()));
+                for relocation in relocations.values() {
+                    relocation.write(&mut writer)?;
+                }
+                relocation_section.content = writer.into_inner();
+            }
+            
+            self.content_sections[&name].relocations = Some(relocations);
+        }
+        
+        self.content_sections[SECTION_NAME].content = content;
+        Ok(())
+    }
+    
     pub fn add_content_section(&mut self, name: impl Into<String>, align: u32, content: Vec<u8>) {
         self.add_content_section_inner(name.into(), align, content);
     }
@@ -118,9 +616,9 @@ impl ElfContainer {
                     sh_offset: 0,
                     // overridden at serialization of container
                     sh_size: 0,
-                    // TODO: determine these fields automatically
-                    sh_link: 4,
-                    sh_info: 1,
+                    // updated by update_section_links
+                    sh_link: 0,
+                    sh_info: 0,
                     sh_addralign: align,
                     sh_entsize: 0xc,
                 },
@@ -171,8 +669,8 @@ impl ElfContainer {
                     sh_offset: 0,
                     // overridden at serialization of container
                     sh_size: 0,
-                    // TODO: determine these fields automatically
-                    sh_link: 5,
+                    // updated by update_section_links
+                    sh_link: 0,
                     sh_info: last_local_symbol,
                     sh_addralign: align,
                     sh_entsize: 0x10,
@@ -186,8 +684,24 @@ impl ElfContainer {
     }
     
     pub fn from_reader(reader: &mut impl Reader) -> Result<Self> {
+        Self::from_reader_inner(reader, true)
+    }
+    
+    /// Reads only the metadata of an elf file, leaving the content of all content sections empty.
+    /// Symbols, relocations and string tables are still read.
+    ///
+    /// This is faster than [`ElfContainer::from_reader`] for just inspecting the symbols of a file.
+    pub fn from_reader_headers_only(reader: &mut impl Reader) -> Result<Self> {
+        Self::from_reader_inner(reader, false)
+    }
+    
+    fn from_reader_inner(reader: &mut impl Reader, load_content: bool) -> Result<Self> {
         let header = ElfHeader::read(reader)?;
         
+        if header.e_machine != EM_PPC {
+            warn!("Unexpected machine type 0x{:x} (expected PowerPC, 0x{EM_PPC:x})", header.e_machine);
+        }
+        
         reader.seek(SeekFrom::Start(header.e_shoff as u64))?;
         
         let section_headers: Vec<SectionHeader> = (0..header.e_shnum)
@@ -195,35 +709,37 @@ impl ElfContainer {
             .collect::<Result<_>>()?;
         
         // Read section header string table
-        let sh_string_table_header = &section_headers[header.e_shstrndx as usize];
-        let mut sh_string_table = vec![0; sh_string_table_header.sh_size as usize];
-        reader.seek(SeekFrom::Start(sh_string_table_header.sh_offset as u64))?;
-        reader.read_exact(&mut sh_string_table)?;
+        let sh_string_table_header = section_headers.get(header.e_shstrndx as usize)
+            .ok_or_else(|| anyhow!("Section header string table index {} is out of bounds \
+                ({} sections)", header.e_shstrndx, section_headers.len()))?;
+        let sh_string_table = read_bytes_at(reader, sh_string_table_header.sh_offset, sh_string_table_header.sh_size)?;
         
         // Read other sections
         let mut all_section_names: Vec<String> = Vec::with_capacity(section_headers.len());
         let mut content_sections: IndexMap<String, Section> = IndexMap::with_capacity(2);
-        let mut meta_sections: IndexMap<String, Section> = IndexMap::with_capacity(section_headers.len() - 1);
+        let mut meta_sections: IndexMap<String, Section> = IndexMap::with_capacity(section_headers.len().saturating_sub(1));
         
         let mut symbol_headers: Option<Vec<SymbolHeader>> = None;
         let mut string_table: Option<Vec<u8>> = None;
         
         for header in section_headers {
             let name = read_string(&sh_string_table, header.sh_name)?.to_string();
-            let section = Section::from_reader(header, name.clone(), reader)?;
+            
+            let is_meta_section = name.starts_with(".rela")
+                || matches!(name.as_str(), ".strtab" | ".symtab" | ".shstrtab");
+            let section = if load_content || is_meta_section {
+                Section::from_reader(header, name.clone(), reader)?
+            } else {
+                Section::from_header(header, name.clone())
+            };
             
             all_section_names.push(name.clone());
             
             if let Some(original_section_name) = name.strip_prefix(".rela") {
-                let relocation_count = section.content.len() / mem::size_of::<Relocation>();
-                let mut reader = Cursor::new(section.content.as_slice());
-                
-                let relocations: IndexMap<Pointer, Relocation> = (0..relocation_count)
-                    .map(|_| match Relocation::read(&mut reader) {
-                        Ok(relocation) => Ok((relocation.offset.into(), relocation)),
-                        Err(err) => Err(err.into()),
-                    })
-                    .collect::<Result<_>>()?;
+                let relocations: IndexMap<Pointer, Relocation> = section.read_entries::<Relocation>()?
+                    .into_iter()
+                    .map(|relocation| (relocation.offset.into(), relocation))
+                    .collect();
                 
                 let original_section: &mut Section = content_sections.get_mut(original_section_name)
                     .ok_or_else(|| anyhow!("Could not find section {}", &name[5..]))?;
@@ -239,14 +755,7 @@ impl ElfContainer {
                     meta_sections.insert(name, section);
                 },
                 ".symtab" => {
-                    let mut reader = Cursor::new(section.content.as_slice());
-                    
-                    let symbol_count = section.content.len() / mem::size_of::<SymbolHeader>();
-                    let symtab: Vec<SymbolHeader> = (0..symbol_count)
-                        .map(|_| SymbolHeader::read(&mut reader).map_err(Error::from))
-                        .collect::<Result<_>>()?;
-                    
-                    symbol_headers = Some(symtab);
+                    symbol_headers = Some(section.read_entries::<SymbolHeader>()?);
                     meta_sections.insert(name, section);
                 },
                 ".shstrtab" => {
@@ -265,20 +774,36 @@ impl ElfContainer {
             bail!("Could not find section .symtab");
         };
         
-        let mut symbols: IndexMap<String, Symbol> = IndexMap::with_capacity(symbol_headers.len());
+        let mut symbols = SymbolTable::with_capacity(symbol_headers.len());
         
-        for sym_header in symbol_headers {
+        for (index, sym_header) in symbol_headers.into_iter().enumerate() {
             let name = if sym_header.st_info == 3 {
                 // section symbol
-                let name = all_section_names.get(sym_header.st_shndx as usize)
-                    .ok_or_else(|| anyhow!("Could not find section with id {}", sym_header.st_shndx))?;
-                
-                name.clone()
+                if let Some(name) = all_section_names.get(sym_header.st_shndx as usize) {
+                    name.clone()
+                } else {
+                    warn!("Section symbol {index} references nonexistent section {}", sym_header.st_shndx);
+                    format!("<section {}>", sym_header.st_shndx)
+                }
             } else {
                 read_string(&string_table, sym_header.st_name)?.to_string()
             };
             
-            symbols.insert(name.clone(), Symbol::new(sym_header, name));
+            symbols.push(Symbol::new(sym_header, name));
+        }
+        
+        // make sure all relocations point to existing symbols
+        for section in content_sections.values() {
+            let Some(relocations) = &section.relocations else {
+                continue;
+            };
+            
+            for relocation in relocations.values() {
+                let symbol_index = relocation.symbol_index();
+                ensure!((symbol_index as usize) < symbols.len(),
+                    "Relocation at offset 0x{:x} in section .rela{} references symbol index {symbol_index}, \
+                    but there are only {} symbols", relocation.offset, section.name, symbols.len());
+            }
         }
         
         Ok(ElfContainer {
@@ -286,21 +811,30 @@ impl ElfContainer {
             symbols,
             content_sections,
             meta_sections,
+            original_section_order: all_section_names,
         })
     }
     
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut writer = Cursor::new(Vec::new());
+        self.write_to(&mut writer)?;
+        Ok(writer.into_inner())
+    }
+    
+    /// Serializes this file into `writer`, which has to be at position 0 because all offsets
+    /// within the file are relative to its start. Afterwards, `writer` is positioned at the end of the file.
+    pub fn write_to(&self, writer: &mut impl Writer) -> Result<()> {
+        ensure!(writer.stream_position()? == 0, "Elf files can only be written at the start of a writer");
         
         // write header
-        self.header.write(&mut writer)?;
+        self.header.write(writer)?;
         
         let mut section_offsets: HashMap<String, Pointer> = HashMap::new();
         
         // write content sections
         for section in self.content_sections.values() {
-            align_to(&mut writer, section.header.sh_addralign as usize)?;
-            section_offsets.insert(section.name.clone(), Pointer::current(&mut writer)?);
+            align_to(writer, section.header.sh_addralign as usize)?;
+            section_offsets.insert(section.name.clone(), Pointer::current(writer)?);
             writer.write_all(&section.content)?;
         }
         
@@ -310,8 +844,8 @@ impl ElfContainer {
                 continue;
             }
             
-            align_to(&mut writer, section.header.sh_addralign as usize)?;
-            section_offsets.insert(section.name.clone(), Pointer::current(&mut writer)?);
+            align_to(writer, section.header.sh_addralign as usize)?;
+            section_offsets.insert(section.name.clone(), Pointer::current(writer)?);
             writer.write_all(&section.content)?;
         }
         
@@ -321,48 +855,47 @@ impl ElfContainer {
                 continue;
             }
             
-            align_to(&mut writer, section.header.sh_addralign as usize)?;
-            section_offsets.insert(section.name.clone(), Pointer::current(&mut writer)?);
+            align_to(writer, section.header.sh_addralign as usize)?;
+            section_offsets.insert(section.name.clone(), Pointer::current(writer)?);
             writer.write_all(&section.content)?;
         }
         
         // write section header table
-        let sh_offset = Pointer::current(&mut writer)?;
+        let sh_offset = Pointer::current(writer)?;
         let shstrtab = &self.meta_sections[".shstrtab"];
         
-        SectionHeader::default().write(&mut writer)?;
+        let order = self.section_header_order();
         
-        for section in self.content_sections.values() {
-            if section.name.is_empty() {
-                continue;
-            }
-            
-            Self::write_section_header(&mut writer, &section_offsets, &shstrtab.content, section)?;
-            
-            let relocation_section = self.meta_sections.get(&format!(".rela{}", &section.name));
-            if let Some(relocation_section) = relocation_section {
-                Self::write_section_header(&mut writer, &section_offsets, &shstrtab.content, relocation_section)?;
-            }
-        }
-        
-        Self::write_section_header(&mut writer, &section_offsets, &shstrtab.content, shstrtab)?;
+        SectionHeader::default().write(writer)?;
         
-        let symtab = &self.meta_sections[".symtab"];
-        Self::write_section_header(&mut writer, &section_offsets, &shstrtab.content, symtab)?;
+        for &name in &order[1..] {
+            let section = self.require_section(name)?;
+            Self::write_section_header(writer, &section_offsets, &shstrtab.content, section)?;
+        }
         
-        let strtab = &self.meta_sections[".strtab"];
-        Self::write_section_header(&mut writer, &section_offsets, &shstrtab.content, strtab)?;
+        // apply section header offset and count, which are derived from what was actually written
+        // instead of trusting the header
+        let end = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(offset_of!(ElfHeader, e_shoff) as u64))?;
+        sh_offset.write(writer)?;
         
-        // apply section header offset
-        writer.set_position(offset_of!(ElfHeader, e_shoff) as u64);
-        sh_offset.write(&mut writer)?;
+        let shstrndx = order.iter().position(|name| *name == ".shstrtab").unwrap();
+        writer.seek(SeekFrom::Start(offset_of!(ElfHeader, e_shnum) as u64))?;
+        (order.len() as u16).write_be(writer)?;
+        (shstrndx as u16).write_be(writer)?;
         
-        Ok(writer.into_inner())
+        writer.seek(SeekFrom::Start(end))?;
+        Ok(())
     }
     
     fn write_section_header(writer: &mut impl Writer, section_offsets: &HashMap<String, Pointer>, shstrtab: &[u8], section: &Section) -> Result<()> {
-        let name_offset = memmem::find(shstrtab, section.name.as_bytes())
-            .unwrap_or(0);
+        // include the null terminator so that names only match at the end of a string
+        let mut terminated_name = Vec::with_capacity(section.name.len() + 1);
+        terminated_name.extend_from_slice(section.name.as_bytes());
+        terminated_name.push(0);
+        
+        let name_offset = memmem::find(shstrtab, &terminated_name)
+            .ok_or_else(|| anyhow!("Section name '{}' is missing from .shstrtab", section.name))?;
         
         let header = SectionHeader {
             sh_name: name_offset as u32,
@@ -1,9 +1,13 @@
-use core::fmt::{self, Debug};
-use std::io::{Read, Seek, SeekFrom};
+use core::{
+    fmt::{self, Debug},
+    mem, slice,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 
-use anyhow::Result;
-use binrw::{BinRead, BinWrite};
+use anyhow::{Result, ensure};
+use binrw::{BinRead, BinWrite, meta::ReadEndian};
 use indexmap::IndexMap;
+use vivibin::util::HashMap;
 
 use crate::util::pointer::Pointer;
 
@@ -40,6 +44,19 @@ pub struct SectionHeader {
 	pub sh_entsize: u32,
 }
 
+/// Reads `size` bytes at `offset`, without allocating all of them upfront so that
+/// an absurd size in a broken header results in an error instead of running out of memory.
+pub fn read_bytes_at<R: Read + Seek>(reader: &mut R, offset: u32, size: u32) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset.into()))?;
+    
+    let mut content = Vec::new();
+    reader.take(size.into()).read_to_end(&mut content)?;
+    ensure!(content.len() == size as usize, "Expected 0x{size:x} bytes at 0x{offset:x}, \
+        but the file ends after 0x{:x} bytes", content.len());
+    
+    Ok(content)
+}
+
 #[derive(Clone, Default)]
 pub struct Section {
     pub header: SectionHeader,
@@ -49,11 +66,18 @@ pub struct Section {
 }
 
 impl Section {
+    /// Creates a section without reading its content, for when only metadata is needed.
+    pub fn from_header(header: SectionHeader, name: String) -> Self {
+        Self {
+            header,
+            name,
+            relocations: None,
+            content: Vec::new(),
+        }
+    }
+    
     pub fn from_reader<R: Read + Seek>(header: SectionHeader, name: String, reader: &mut R) -> Result<Self> {
-        reader.seek(SeekFrom::Start(header.sh_offset as u64))?;
-        
-        let mut content: Vec<u8> = vec![0; header.sh_size as usize];
-        reader.read_exact(&mut content)?;
+        let content = read_bytes_at(reader, header.sh_offset, header.sh_size)?;
         
         Ok(Self {
             header,
@@ -62,6 +86,53 @@ impl Section {
             content,
         })
     }
+    
+    /// Size of one entry of a table like `.symtab`, which is `sh_entsize` if it is set
+    /// and the size of `T` otherwise.
+    pub fn entry_size<T>(&self) -> usize {
+        match self.header.sh_entsize {
+            0 => mem::size_of::<T>(),
+            entry_size => entry_size as usize,
+        }
+    }
+    
+    /// Amount of entries in a table, see [`Section::entry_size`].
+    pub fn entry_count<T>(&self) -> usize {
+        self.content.len() / self.entry_size::<T>()
+    }
+    
+    /// Reads every entry of a table, skipping over any bytes past the end of `T` if `sh_entsize` is larger.
+    pub fn read_entries<T>(&self) -> Result<Vec<T>>
+    where
+        T: ReadEndian + for<'a> BinRead<Args<'a> = ()>,
+    {
+        let entry_size = self.entry_size::<T>();
+        ensure!(entry_size >= mem::size_of::<T>(), "Section {} has entries of 0x{entry_size:x} bytes, \
+            expected at least 0x{:x}", self.name, mem::size_of::<T>());
+        
+        let mut reader = Cursor::new(self.content.as_slice());
+        
+        (0..self.entry_count::<T>())
+            .map(|i| {
+                reader.set_position((i * entry_size) as u64);
+                Ok(T::read(&mut reader)?)
+            })
+            .collect()
+    }
+    
+    /// Iterates over the relocations of this section as the offset they are applied at,
+    /// the symbol they point to and the addend relative to that symbol.
+    ///
+    /// Relocations pointing to symbols that do not exist are skipped,
+    /// [`ElfContainer::verify`](container::ElfContainer::verify) reports those.
+    pub fn resolved_relocations<'a>(&'a self, symbols: &'a SymbolTable) -> impl Iterator<Item = (Pointer, &'a Symbol, u32)> {
+        self.relocations.iter()
+            .flat_map(|relocations| relocations.iter())
+            .filter_map(|(&offset, relocation)| {
+                let symbol = symbols.get_index(relocation.symbol_index() as usize)?;
+                Some((offset, symbol, relocation.addend))
+            })
+    }
 }
 
 impl Debug for Section {
@@ -74,6 +145,12 @@ impl Debug for Section {
     }
 }
 
+/// Relocation type writing the absolute 32-bit address of the symbol, the only one used in the game's data files.
+pub const R_PPC_ADDR32: u8 = 1;
+
+/// Symbol binding (upper four bits of `st_info`) of symbols that are visible outside of their file.
+pub const STB_GLOBAL: u8 = 1;
+
 #[derive(Debug, Clone, BinRead, BinWrite)]
 #[brw(big)]
 pub struct Relocation {
@@ -90,6 +167,20 @@ impl Relocation {
             addend,
         }
     }
+    
+    pub fn with_symbol(offset: u32, symbol_index: u32, reloc_type: u8, addend: u32) -> Self {
+        Self::new(offset, symbol_index << 8 | reloc_type as u32, addend)
+    }
+    
+    /// Index into the symbol table of the symbol this relocation points to.
+    pub fn symbol_index(&self) -> u32 {
+        self.info >> 8
+    }
+    
+    /// Type of the relocation, currently only [`R_PPC_ADDR32`] is handled.
+    pub fn reloc_type(&self) -> u8 {
+        self.info as u8
+    }
 }
 
 #[derive(Debug, Clone, Default, BinRead, BinWrite)]
@@ -123,14 +214,125 @@ impl Symbol {
     }
 }
 
+/// Symbols of an elf file in the same order as in its symbol table, so that relocations can
+/// refer to them by index, along with a lookup by name.
+///
+/// Symbol names are not unique (e.g. section symbols or the null symbol), in which case
+/// looking up a name returns the first symbol with that name.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    indices: HashMap<String, usize>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            symbols: Vec::with_capacity(capacity),
+            indices: HashMap::with_capacity(capacity),
+        }
+    }
+    
+    pub fn push(&mut self, symbol: Symbol) {
+        self.indices.entry(symbol.name.clone()).or_insert(self.symbols.len());
+        self.symbols.push(symbol);
+    }
+    
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.indices.get(name).map(|&index| &self.symbols[index])
+    }
+    
+    pub fn get_index(&self, index: usize) -> Option<&Symbol> {
+        self.symbols.get(index)
+    }
+    
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+    
+    pub fn contains(&self, name: &str) -> bool {
+        self.indices.contains_key(name)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+    
+    pub fn iter(&self) -> slice::Iter<'_, Symbol> {
+        self.symbols.iter()
+    }
+    
+    /// Returns every symbol whose range `[st_value, st_value + st_size)` contains `offset`.
+    ///
+    /// Symbols of all sections are considered, since their headers only refer to the section by index.
+    pub fn symbols_at_offset(&self, offset: u32) -> Vec<&Symbol> {
+        self.symbols.iter()
+            .filter(|symbol| (symbol.offset()..symbol.offset().saturating_add(symbol.size())).contains(&offset))
+            .collect()
+    }
+    
+    /// Mutable access to the symbol headers, names can only be changed using [`SymbolTable::rename`].
+    pub fn headers_mut(&mut self) -> impl Iterator<Item = &mut SymbolHeader> {
+        self.symbols.iter_mut().map(|symbol| &mut symbol.header)
+    }
+    
+    /// Renames the symbol at `index`, keeping the name lookup up to date.
+    pub fn rename(&mut self, index: usize, new_name: String) {
+        let symbol = &mut self.symbols[index];
+        let old_name = mem::replace(&mut symbol.name, new_name.clone());
+        
+        if self.indices.get(&old_name) == Some(&index) {
+            self.indices.remove(&old_name);
+            
+            // another symbol with the old name might still exist
+            if let Some(other_index) = self.symbols.iter().position(|symbol| symbol.name == old_name) {
+                self.indices.insert(old_name, other_index);
+            }
+        }
+        
+        let new_index = self.indices.entry(new_name).or_insert(index);
+        *new_index = (*new_index).min(index);
+    }
+}
+
+impl<'a> IntoIterator for &'a SymbolTable {
+    type Item = &'a Symbol;
+    type IntoIter = slice::Iter<'a, Symbol>;
+    
+    fn into_iter(self) -> Self::IntoIter {
+        self.symbols.iter()
+    }
+}
+
 pub const AUTO_SYMBOL_NAME_CHAR_COUNT: usize = 93;
 pub const AUTO_SYMBOL_NAME_CHARS: &[u8; AUTO_SYMBOL_NAME_CHAR_COUNT] = 
     b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!@$%^&*()_+-=[]{};\'\\:\"|,./<>?~`";
 
-#[derive(Default)]
 pub struct SymbolNameGenerator {
     indices: Vec<usize>,
     result: Vec<u8>,
+    
+    /// Whether the first character skips 'a' after overflowing, which is the case
+    /// for all formats supported so far. Enabled by default.
+    pub skip_first_a: bool,
+}
+
+impl Default for SymbolNameGenerator {
+    fn default() -> Self {
+        Self {
+            indices: Vec::new(),
+            result: Vec::new(),
+            skip_first_a: true,
+        }
+    }
 }
 
 impl SymbolNameGenerator {
@@ -138,6 +340,15 @@ impl SymbolNameGenerator {
         Self::default()
     }
     
+    pub fn with_skip_first_a(mut self, skip_first_a: bool) -> Self {
+        self.skip_first_a = skip_first_a;
+        self
+    }
+    
+    /// Returns the next name in the sequence "", "b", "c", ... "`", "ba", "bb", ...
+    ///
+    /// The first name is empty and single character names start at 'b' because the very first
+    /// call already puts an 'a' into the result without returning it.
     // since this is a lending iterator, which is not compatible with std::Iterator
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> &str {
@@ -164,12 +375,17 @@ impl SymbolNameGenerator {
         }
     }
     
+    /// Counts up the character at `index` like a digit in base [`AUTO_SYMBOL_NAME_CHAR_COUNT`],
+    /// returning whether it wrapped around so that the carry has to be added to the character before it.
+    ///
+    /// When the first character wraps around, [`SymbolNameGenerator::next`] appends a new character
+    /// to the name, so the first character restarts at 'b' to keep names from having a leading 'a'.
     fn count_up_check_overflow(&mut self, index: usize) -> bool {
         let value = &mut self.indices[index];
         *value += 1;
         
         let overflow = *value >= AUTO_SYMBOL_NAME_CHAR_COUNT;
-        if overflow && index == 0 {
+        if overflow && index == 0 && self.skip_first_a {
             // first character for some reason is never 'a'
             *value = 1;
         } else if overflow {
@@ -0,0 +1,54 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::formats::{FileType, FileTypeMismatch};
+
+/// Error type returned by the public entry points of this crate, so that they can be matched on.
+///
+/// Internally, errors are passed around as [`anyhow::Error`] and only converted at the boundary.
+#[derive(Debug, Error)]
+pub enum PaintelfError {
+    /// `suggestions` contains the names of the most similar symbols that do exist.
+    #[error("Could not find symbol {name:?}{}", did_you_mean(suggestions))]
+    SymbolNotFound { name: String, suggestions: Vec<String> },
+    #[error("Offset 0x{offset:x} out of bounds (len 0x{len:x})")]
+    PointerOutOfBounds { offset: u32, len: usize },
+    #[error("Rebuilding {0} files is not supported yet")]
+    UnsupportedFileType(FileType),
+    #[error(transparent)]
+    FileTypeMismatch(#[from] FileTypeMismatch),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    
+    let suggestions: Vec<String> = suggestions.iter()
+        .map(|suggestion| format!("{suggestion:?}"))
+        .collect();
+    format!(", did you mean {}?", suggestions.join(", "))
+}
+
+impl From<anyhow::Error> for PaintelfError {
+    fn from(value: anyhow::Error) -> Self {
+        let value = match value.downcast::<PaintelfError>() {
+            Ok(err) => return err,
+            Err(value) => value,
+        };
+        let value = match value.downcast::<FileTypeMismatch>() {
+            Ok(err) => return err.into(),
+            Err(value) => value,
+        };
+        
+        match value.downcast::<io::Error>() {
+            Ok(err) => PaintelfError::Io(err),
+            Err(value) => PaintelfError::Other(value),
+        }
+    }
+}
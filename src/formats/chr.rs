@@ -1,91 +1,174 @@
-use std::{borrow::Cow, io::SeekFrom};
-
 use anyhow::Result;
-use byteorder::{BigEndian, ReadBytesExt};
+use log::warn;
 use serde::{Deserialize, Serialize};
-use vivibin::{CanRead, Readable, Reader, Writable};
+use vivibin::{CanRead, CanWrite, CanWriteBox, HeapCategory, Readable, Reader, Writable, WriteCtx};
 
-use crate::{binutil::ElfReadDomain, formats::FileData, scoped_reader_pos, util::pointer::Pointer};
+use crate::{
+    SymbolName,
+    binutil::{DataCategory, ElfReadDomain, ElfWriteDomain, WriteStringArgs},
+    formats::FileData,
+};
 
 pub fn read_chr(reader: &mut impl Reader, domain: ElfReadDomain) -> Result<FileData> {
-    eprintln!("Warning: data_chr is not fully supported yet. The yaml format is not final yet \
-    and rebuilding the elf is not implemented yet.");
-    
-    // npcs
-    let npc_count_symbol = domain.find_symbol("npcDataTableLen__Q2_4data3chr")?;
-    reader.seek(SeekFrom::Start(npc_count_symbol.offset().into()))?;
-    let npc_count = reader.read_u32::<BigEndian>()?;
-    
-    let npc_data_symbol = domain.find_symbol("npcDataTable__Q2_4data3chr")?;
-    reader.seek(SeekFrom::Start(npc_data_symbol.offset().into()))?;
+    warn!("data_chr is not fully supported yet. The yaml format is not final yet \
+    and the model, player and party tables are not read, so they will be missing when rebuilding.");
     
-    let npc_data: Vec<NpcDefPtr> = (0..npc_count - 1)
-        .map(|_| NpcDefPtr::from_reader(reader, domain))
-        .collect::<Result<_>>()?;
+    let npc_data: Vec<NpcDefPtr> = domain.read_counted_table(
+        reader, "npcDataTableLen__Q2_4data3chr", "npcDataTable__Q2_4data3chr", true,
+        |reader| NpcDefPtr::from_reader(reader, domain),
+    )?;
     
-    // mobjs
-    let mobj_count_symbol = domain.find_symbol("mobjDataTableLen__Q2_4data3chr")?;
-    reader.seek(SeekFrom::Start(mobj_count_symbol.offset().into()))?;
-    let mobj_count = reader.read_u32::<BigEndian>()?;
-    
-    let mobj_data_symbol = domain.find_symbol("mobjDataTable__Q2_4data3chr")?;
-    reader.seek(SeekFrom::Start(mobj_data_symbol.offset().into()))?;
-    
-    let mobj_data: Vec<MobjDefPtr> = (0..mobj_count - 1)
-        .map(|_| MobjDefPtr::from_reader(reader, domain))
-        .collect::<Result<_>>()?;
+    let mobj_data: Vec<MobjDefPtr> = domain.read_counted_table(
+        reader, "mobjDataTableLen__Q2_4data3chr", "mobjDataTable__Q2_4data3chr", true,
+        |reader| MobjDefPtr::from_reader(reader, domain),
+    )?;
     
     Ok(FileData::Chr(ChrData {
-        models: Cow::Borrowed("TODO"),
-        kusya_models: Cow::Borrowed("TODO"),
-        painky_models: Cow::Borrowed("TODO"),
-        
         npc_data,
         mobj_data,
-        player_data: Cow::Borrowed("TODO"),
-        party_data: Cow::Borrowed("TODO"),
     }))
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChrData {
-    pub models: Cow<'static, str>,
-    pub kusya_models: Cow<'static, str>,
-    pub painky_models: Cow<'static, str>,
+/// Writes the npc and mobj tables, each as a null terminated array of pointers to the definitions.
+pub fn write_chr(ctx: &mut impl WriteCtx<DataCategory>, domain: &mut ElfWriteDomain, chr: &ChrData) -> Result<()> {
+    domain.write_null_terminated_counted_table(
+        ctx, "npcDataTableLen__Q2_4data3chr", "npcDataTable__Q2_4data3chr", &chr.npc_data,
+        |domain, ctx, npc| npc.to_writer(ctx, domain),
+    )?;
     
+    domain.write_null_terminated_counted_table(
+        ctx, "mobjDataTableLen__Q2_4data3chr", "mobjDataTable__Q2_4data3chr", &chr.mobj_data,
+        |domain, ctx, mobj| mobj.to_writer(ctx, domain),
+    )?;
+    
+    Ok(())
+}
+
+// TODO: add the model, kusya model, painky model, player and party tables
+// once their symbols and layout have been checked against data_chr.elf
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChrData {
     pub npc_data: Vec<NpcDefPtr>,
     pub mobj_data: Vec<MobjDefPtr>,
-    pub player_data: Cow<'static, str>,
-    pub party_data: Cow<'static, str>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NpcDefPtr(NpcDef);
+/// Pointer to an [`NpcDef`], as stored in the tables of [`ChrData`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NpcDefPtr(pub NpcDef);
+
+impl Readable<ElfReadDomain<'_>> for NpcDefPtr {
+    fn from_reader_unboxed<R: Reader>(reader: &mut R, domain: ElfReadDomain<'_>) -> Result<Self> {
+        domain.read_boxed(reader, |reader| NpcDef::from_reader(reader, domain)).map(NpcDefPtr)
+    }
+}
+
+impl<C: HeapCategory, D: CanWriteBox<C>> Writable<C, D> for NpcDefPtr
+where
+    NpcDef: Writable<C, D>,
+{
+    type UnboxedPostState = ();
+    type PostState = ();
+    
+    fn to_writer_unboxed(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D) -> Result<()> {
+        self.0.to_writer(ctx, domain)
+    }
+    
+    fn to_writer(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D) -> Result<()> {
+        domain.write_box_of(ctx, |domain, ctx| {
+            self.to_writer_unboxed(ctx, domain)
+        })
+    }
+    
+    fn to_writer_post(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D, state: Self::PostState) -> Result<()> {
+        self.to_writer_unboxed_post(ctx, domain, state)
+    }
+}
+
+/// Pointer to a [`MobjDef`], as stored in the mobj table of [`ChrData`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MobjDefPtr(pub MobjDef);
+
+impl Readable<ElfReadDomain<'_>> for MobjDefPtr {
+    fn from_reader_unboxed<R: Reader>(reader: &mut R, domain: ElfReadDomain<'_>) -> Result<Self> {
+        domain.read_boxed(reader, |reader| MobjDef::from_reader(reader, domain)).map(MobjDefPtr)
+    }
+}
 
-impl<D: CanRead<String> + CanRead<Option<String>> + CanRead<Pointer>> Readable<D> for NpcDefPtr {
-    fn from_reader_unboxed<R: Reader>(reader: &mut R, domain: D) -> Result<Self> {
-        let ptr: Pointer = domain.read(reader)?;
-        scoped_reader_pos!(reader);
-        
-        reader.seek(SeekFrom::Start(ptr.into()))?;
-        NpcDef::from_reader(reader, domain).map(NpcDefPtr)
+impl<C: HeapCategory, D: CanWriteBox<C>> Writable<C, D> for MobjDefPtr
+where
+    MobjDef: Writable<C, D>,
+{
+    type UnboxedPostState = ();
+    type PostState = ();
+    
+    fn to_writer_unboxed(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D) -> Result<()> {
+        self.0.to_writer(ctx, domain)
     }
+    
+    fn to_writer(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D) -> Result<()> {
+        domain.write_box_of(ctx, |domain, ctx| {
+            self.to_writer_unboxed(ctx, domain)
+        })
+    }
+    
+    fn to_writer_post(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D, state: Self::PostState) -> Result<()> {
+        self.to_writer_unboxed_post(ctx, domain, state)
+    }
+}
+
+/// Models of an npc or mobj, stored as a pointer to a null terminated array of model names.
+///
+/// Null pointers are read as [`ModelList::None`], which is `null` in yaml.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ModelList {
+    Models(Vec<String>),
+    #[default]
+    None,
 }
 
-#[derive(Debug, Clone, Readable, Writable, Serialize, Deserialize)]
+impl CanRead<ModelList> for ElfReadDomain<'_> {
+    fn read(self, reader: &mut impl Reader) -> Result<ModelList> {
+        match self.read_pointer_optional(reader)? {
+            Some(ptr) => Ok(ModelList::Models(self.read_null_terminated_string_array(reader, ptr)?)),
+            None => Ok(ModelList::None),
+        }
+    }
+}
+
+impl CanWrite<DataCategory, ModelList> for ElfWriteDomain {
+    fn write(&mut self, ctx: &mut impl WriteCtx<DataCategory>, value: &ModelList) -> Result<()> {
+        match value {
+            ModelList::Models(models) => {
+                let symbol_name = SymbolName::Internal(self.box_symbol_char);
+                self.write_box(ctx, Some(symbol_name), |domain, ctx| {
+                    for model in models {
+                        domain.write_string(ctx, model, WriteStringArgs::default())?;
+                    }
+                    0u32.to_writer(ctx, domain)
+                })
+            },
+            ModelList::None => 0u32.to_writer(ctx, self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Readable, Writable, Serialize, Deserialize)]
 pub struct NpcDef {
     #[require_domain]
     pub id: String,
     pub description: String,
     #[require_domain]
-    pub model_ptr: Pointer,
+    pub models: ModelList,
     pub field_0xc: String,
+    #[serde(default)]
     pub field_0x10: u32,
     pub field_0x14: String,
     #[require_domain]
     pub field_0x18: Option<String>,
     pub field_0x1c: Option<String>,
     pub main_function: Option<String>,
+    #[serde(default)]
     pub field_0x24: u32,
     pub action_function: Option<String>,
     pub field_0x2c: Option<String>,
@@ -94,60 +177,62 @@ pub struct NpcDef {
     pub field_0x38: Option<String>,
     pub field_0x3c: Option<String>,
     pub field_0x40: Option<String>,
+    #[serde(default)]
     pub field_0x44: u32,
     pub field_0x48: Option<String>,
     pub field_0x4c: Option<String>,
+    #[serde(default, with = "crate::util::exact_f32")]
     pub field_0x50: f32,
+    #[serde(default, with = "crate::util::exact_f32")]
     pub field_0x54: f32,
+    #[serde(default)]
     pub field_0x58: u32,
+    #[serde(default)]
     pub field_0x5c: u32,
+    #[serde(default)]
     pub field_0x60: u32,
+    #[serde(default)]
     pub field_0x64: u32,
     pub field_0x68: Option<String>,
     pub field_0x6c: Option<String>,
     pub field_0x70: Option<String>,
+    #[serde(default)]
     pub field_0x74: u32,
     pub field_0x78: Option<String>,
     pub field_0x7c: Option<String>,
     pub field_0x80: Option<String>,
     pub field_0x84: Option<String>,
     pub field_0x88: Option<String>,
+    #[serde(default, with = "crate::util::exact_f32")]
     pub field_0x8c: f32,
+    #[serde(default)]
     pub field_0x90: u32,
     pub field_0x94: Option<String>,
     pub field_0x98: Option<String>,
     pub field_0x9c: Option<String>,
+    #[serde(default, with = "crate::util::exact_f32")]
     pub field_0xa0: f32,
     pub field_0xa4: Option<String>,
+    #[serde(default, with = "crate::util::exact_f32")]
     pub field_0xa8: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MobjDefPtr(MobjDef);
-
-impl<D: CanRead<String> + CanRead<Option<String>> + CanRead<Pointer>> Readable<D> for MobjDefPtr {
-    fn from_reader_unboxed<R: Reader>(reader: &mut R, domain: D) -> Result<Self> {
-        let ptr: Pointer = domain.read(reader)?;
-        scoped_reader_pos!(reader);
-        
-        reader.seek(SeekFrom::Start(ptr.into()))?;
-        MobjDef::from_reader(reader, domain).map(MobjDefPtr)
-    }
-}
-
-#[derive(Debug, Clone, Readable, Writable, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Readable, Writable, Serialize, Deserialize)]
 pub struct MobjDef {
     #[require_domain]
     pub id: String,
     pub description: String,
     #[require_domain]
-    pub model_ptr: Pointer,
+    pub models: ModelList,
+    #[serde(default)]
     pub field_0xc: u32,
     pub field_0x10: String,
     pub field_0x14: String,
     pub field_0x18: String,
     pub field_0x1c: String,
+    #[serde(default)]
     pub field_0x20: u32,
+    #[serde(default)]
     pub field_0x24: u32,
     #[require_domain]
     pub field_0x28: Option<String>,
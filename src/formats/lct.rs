@@ -1,11 +1,9 @@
-use std::io::SeekFrom;
-
 use anyhow::Result;
-use byteorder::{BigEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
 use vivibin::{
-    CanWrite, CanWriteBox, CanWriteSlice, CanWriteSliceWithArgs, HeapCategory, Readable, Reader,
-    Writable, WriteCtx, WriteSliceFallbackExt, WriteSliceWithArgsFallbackExt,
+    CanRead, CanWrite, CanWriteBox, CanWriteSlice, CanWriteSliceWithArgs, HeapCategory, Readable,
+    Reader, Writable, WriteCtx, WriteSliceFallbackExt, WriteSliceWithArgsFallbackExt,
+    default_to_writer_impl,
 };
 
 use crate::{
@@ -14,37 +12,22 @@ use crate::{
 };
 
 pub fn read_lct(reader: &mut impl Reader, domain: ElfReadDomain) -> Result<FileData> {
-    let data_count_symbol = domain.find_symbol("all_lctAnimeDataTblLen__Q2_4data3lct")?;
-    reader.seek(SeekFrom::Start(data_count_symbol.offset().into()))?;
-    let data_count = reader.read_u32::<BigEndian>()?;
-    
-    let datas_symbol = domain.find_symbol("all_lctAnimeDataTbl__Q2_4data3lct")?;
-    reader.seek(SeekFrom::Start(datas_symbol.offset().into()))?;
-    
-    let areas: Vec<AreaLct> = (0..data_count - 1)
-        .map(|_| AreaLct::from_reader(reader, domain))
-        .collect::<Result<_>>()?;
+    let areas: Vec<AreaLct> = domain.read_counted_table(
+        reader, "all_lctAnimeDataTblLen__Q2_4data3lct", "all_lctAnimeDataTbl__Q2_4data3lct", true,
+        |reader| AreaLct::from_reader(reader, domain),
+    )?;
     
     Ok(FileData::Lct(areas))
 }
 
 pub fn write_lct(ctx: &mut impl WriteCtx<DataCategory>, domain: &mut ElfWriteDomain, lcts: &[AreaLct]) -> Result<()> {
-    domain.write_symbol(ctx, "all_lctAnimeDataTblLen__Q2_4data3lct", |domain, ctx| {
-        (lcts.len() as u32 + 1).to_writer(ctx, domain)
-    })?;
-    
-    domain.write_symbol(ctx, "all_lctAnimeDataTbl__Q2_4data3lct", |domain, ctx| {
-        for lct in lcts {
-            lct.to_writer(ctx, domain)?;
-        }
-        0u32.to_writer(ctx, domain)?;
-        Ok(())
-    })?;
-    
-    Ok(())
+    domain.write_null_terminated_counted_table(
+        ctx, "all_lctAnimeDataTblLen__Q2_4data3lct", "all_lctAnimeDataTbl__Q2_4data3lct", lcts,
+        |domain, ctx, lct| lct.to_writer(ctx, domain),
+    )
 }
 
-#[derive(Clone, Debug, Readable, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Readable, Deserialize, Serialize)]
 #[boxed]
 pub struct AreaLct {
     #[require_domain]
@@ -83,7 +66,7 @@ where
     }
 }
 
-#[derive(Clone, Debug, Default, Readable, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Readable, Deserialize, Serialize)]
 #[boxed]
 pub struct MapLct {
     #[require_domain]
@@ -117,11 +100,61 @@ impl<C: HeapCategory, D: CanWrite<C, String> + CanWriteBox<C> + CanWriteSlice<C>
     }
 }
 
-#[derive(Clone, Debug, Readable, Writable, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Readable, Writable, Deserialize, Serialize)]
 pub struct Lct {
     #[require_domain]
     pub id: String,
     pub directory: String,
     pub file_name: String,
-    pub field_0xc: u32,
+    pub field_0xc: LctKind,
+}
+
+/// Typed view of `Lct::field_0xc`, which is still written into yaml as its plain number.
+///
+/// The meaning of this field is not known yet, and the values it takes in data_lct.elf
+/// have not been collected, since no copy of that file is part of this repository.
+/// 0 ([`LctKind::None`]) is just the default for newly created lcts, every other value
+/// is kept as [`LctKind::Unknown`] so files still round-trip.
+// TODO: add a variant for each value that occurs in data_lct.elf once it has been looked at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u32", into = "u32")]
+pub enum LctKind {
+    #[default]
+    None,
+    Unknown(u32),
+}
+
+impl From<u32> for LctKind {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => LctKind::None,
+            value => LctKind::Unknown(value),
+        }
+    }
+}
+
+impl From<LctKind> for u32 {
+    fn from(value: LctKind) -> Self {
+        match value {
+            LctKind::None => 0,
+            LctKind::Unknown(value) => value,
+        }
+    }
+}
+
+impl<D: CanRead<u32>> Readable<D> for LctKind {
+    fn from_reader_unboxed<R: Reader>(reader: &mut R, domain: D) -> Result<Self> {
+        let value: u32 = domain.read(reader)?;
+        Ok(LctKind::from(value))
+    }
+}
+
+impl<C: HeapCategory, D: CanWrite<C, u32>> Writable<C, D> for LctKind {
+    type UnboxedPostState = ();
+    
+    fn to_writer_unboxed(&self, ctx: &mut impl WriteCtx<C>, domain: &mut D) -> Result<()> {
+        u32::from(*self).to_writer(ctx, domain)
+    }
+    
+    default_to_writer_impl!(C);
 }
@@ -1,27 +1,24 @@
-use std::io::SeekFrom;
-
 use anyhow::Result;
-use byteorder::{BigEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
 use vivibin::{Readable, Reader, Writable, WriteCtx};
 
 use crate::{
     SymbolName,
-    binutil::{DataCategory, ElfReadDomain, ElfWriteDomain, WriteSliceArgs, WriteStringArgs},
+    binutil::{
+        DataCategory, ElfReadDomain, ElfWriteDomain, WriteSliceArgs, WriteStringArgs,
+        check_count_matches_symbol,
+    },
     formats::FileData,
 };
 
 pub fn read_mapid(reader: &mut impl Reader, domain: ElfReadDomain) -> Result<FileData> {
-    let data_count_symbol = domain.find_symbol("dataCount__Q3_4data3fld5mapid")?;
-    reader.seek(SeekFrom::Start(data_count_symbol.offset().into()))?;
-    let data_count = reader.read_u32::<BigEndian>()?;
+    let areas: Vec<MapGroup> = domain.read_counted_table(
+        reader, "dataCount__Q3_4data3fld5mapid", "datas__Q3_4data3fld5mapid", false,
+        |reader| MapGroup::from_reader(reader, domain),
+    )?;
     
     let datas_symbol = domain.find_symbol("datas__Q3_4data3fld5mapid")?;
-    reader.seek(SeekFrom::Start(datas_symbol.offset().into()))?;
-    
-    let areas: Vec<MapGroup> = (0..data_count)
-        .map(|_| MapGroup::from_reader(reader, domain))
-        .collect::<Result<_>>()?;
+    check_count_matches_symbol(areas.len() as u32, &datas_symbol, MAP_GROUP_SIZE);
     
     Ok(FileData::MapId(areas))
 }
@@ -41,7 +38,10 @@ pub fn write_mapid(ctx: &mut impl WriteCtx<DataCategory>, domain: &mut ElfWriteD
     Ok(())
 }
 
-#[derive(Clone, Debug, Readable, Writable, Serialize, Deserialize)]
+/// Size of a serialized [`MapGroup`] in bytes
+pub const MAP_GROUP_SIZE: u32 = 0xc;
+
+#[derive(Clone, Debug, PartialEq, Readable, Writable, Serialize, Deserialize)]
 pub struct MapGroup {
     #[require_domain]
     #[write_args(WriteStringArgs { deduplicate: false })]
@@ -49,11 +49,12 @@ pub struct MapGroup {
     
     #[write_args(WriteSliceArgs {
         symbol_name: Some(SymbolName::InternalNamed(self.id.clone())),
+        count_first: false,
     })]
     pub maps: Vec<MapDefinition>,
 }
 
-#[derive(Debug, Clone, Readable, Writable, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Readable, Writable, Serialize, Deserialize)]
 pub struct MapDefinition {
     #[require_domain]
     pub group_id: String,
@@ -64,18 +65,29 @@ pub struct MapDefinition {
     pub field_0x14: String,
     pub field_0x18: String,
     pub field_0x1c: String,
+    #[serde(default)]
     pub field_0x20: u32,
     pub field_0x24: String,
     pub field_0x28: String,
+    #[serde(default)]
     pub field_0x2c: u32,
+    #[serde(default)]
     pub field_0x30: u32,
+    #[serde(default)]
     pub field_0x34: u32,
+    #[serde(default)]
     pub field_0x38: u32,
+    #[serde(default)]
     pub field_0x3c: u32,
+    #[serde(default)]
     pub field_0x40: u32,
+    #[serde(default)]
     pub field_0x44: u32,
+    #[serde(default)]
     pub field_0x48: u32,
+    #[serde(default)]
     pub field_0x4c: u32,
+    #[serde(default)]
     pub field_0x50: u32,
     pub field_0x54: String,
     pub field_0x58: String,
@@ -1,27 +1,24 @@
-use std::io::SeekFrom;
-
 use anyhow::Result;
-use byteorder::{BigEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
 use vivibin::{Readable, Reader, Writable, WriteCtx};
 
 use crate::{
     SymbolName,
-    binutil::{DataCategory, ElfReadDomain, ElfWriteDomain, WriteSliceArgs, WriteStringArgs},
+    binutil::{
+        DataCategory, ElfReadDomain, ElfWriteDomain, WriteSliceArgs, WriteStringArgs,
+        check_count_matches_symbol,
+    },
     formats::FileData,
 };
 
 pub fn read_maplink(reader: &mut impl Reader, domain: ElfReadDomain) -> Result<FileData> {
-    let data_count_symbol = domain.find_symbol("dataCount__Q3_4data3fld7maplink")?;
-    reader.seek(SeekFrom::Start(data_count_symbol.offset().into()))?;
-    let data_count = reader.read_u32::<BigEndian>()?;
+    let areas: Vec<MaplinkArea> = domain.read_counted_table(
+        reader, "dataCount__Q3_4data3fld7maplink", "datas__Q3_4data3fld7maplink", false,
+        |reader| MaplinkArea::from_reader(reader, domain),
+    )?;
     
     let datas_symbol = domain.find_symbol("datas__Q3_4data3fld7maplink")?;
-    reader.seek(SeekFrom::Start(datas_symbol.offset().into()))?;
-    
-    let areas: Vec<MaplinkArea> = (0..data_count)
-        .map(|_| MaplinkArea::from_reader(reader, domain))
-        .collect::<Result<_>>()?;
+    check_count_matches_symbol(areas.len() as u32, &datas_symbol, MAPLINK_AREA_SIZE);
     
     Ok(FileData::Maplink(areas))
 }
@@ -41,7 +38,10 @@ pub fn write_maplink(ctx: &mut impl WriteCtx<DataCategory>, domain: &mut ElfWrit
     Ok(())
 }
 
-#[derive(Clone, Debug, Readable, Writable, Serialize, Deserialize)]
+/// Size of a serialized [`MaplinkArea`] in bytes
+pub const MAPLINK_AREA_SIZE: u32 = 0xc;
+
+#[derive(Clone, Debug, PartialEq, Readable, Writable, Serialize, Deserialize)]
 pub struct MaplinkArea {
     #[require_domain]
     #[write_args(WriteStringArgs { deduplicate: false })]
@@ -49,23 +49,26 @@ pub struct MaplinkArea {
     
     #[write_args(WriteSliceArgs {
         symbol_name: Some(SymbolName::InternalNamed(self.map_name.clone())),
+        count_first: false,
     })]
     pub links: Vec<Link>,
 }
 
-#[derive(Clone, Debug, Readable, Writable, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Readable, Writable, Serialize, Deserialize)]
 pub struct Link {
     #[require_domain]
     pub id: String,
     pub destination: String,
     pub link_type: String,
     pub zone_id: String,
+    #[serde(with = "crate::util::exact_f32")]
     pub player_direction: f32,
     pub player_facing: String,
     pub door_type: String,
     pub field_0x1c: String,
     pub pipe_cam_script_enter: String,
     pub pipe_cam_script_exit: String,
+    #[serde(default)]
     pub field_0x28: u32,
     pub field_0x2c: String,
     pub enter_function: String,
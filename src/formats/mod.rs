@@ -1,9 +1,16 @@
-use core::fmt::{self, Display};
+use core::{
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
+use std::collections::HashSet;
 
+use anyhow::{Result, bail, ensure};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     binutil::ElfCategoryType,
+    elf::SymbolTable,
     formats::{
         chr::ChrData, dispos::DisposArea, lct::AreaLct, mapid::MapGroup, maplink::MaplinkArea,
         shop::Shop,
@@ -17,7 +24,7 @@ pub mod mapid;
 pub mod maplink;
 pub mod shop;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FileType {
     Maplink,
     MapId,
@@ -28,26 +35,77 @@ pub enum FileType {
 }
 
 impl FileType {
+    pub const ALL: &[FileType] = &[
+        FileType::Maplink,
+        FileType::MapId,
+        FileType::Shop,
+        FileType::Dispos,
+        FileType::Chr,
+        FileType::Lct,
+    ];
     pub const ALL_VALUES: &[&str] = &["maplink", "mapid", "shop", "dispos", "chr", "lct"];
     
+    /// Parses a file type name, see the [`FromStr`] implementation.
     pub fn from_string(string: &str) -> Option<FileType> {
-        match string {
-            "maplink" => Some(FileType::Maplink),
-            "mapid" => Some(FileType::MapId),
-            "shop" => Some(FileType::Shop),
-            "dispos" => Some(FileType::Dispos),
-            "chr" => Some(FileType::Chr),
-            "lct" => Some(FileType::Lct),
-            _ => None,
-        }
+        string.parse().ok()
     }
     
+    /// Name of the section containing the data, which rebuilt files always use.
     pub fn content_section_name(self) -> &'static str {
+        self.content_section_names()[0]
+    }
+    
+    /// Names the content section may have in existing files, in the order they are looked up.
+    pub fn content_section_names(self) -> &'static [&'static str] {
+        match self {
+            FileType::Dispos | FileType::Chr | FileType::Lct => &[".data", ".sdata"],
+            _ => &[".rodata", ".sdata2"],
+        }
+    }
+    
+    /// Name of the source file that the original elf file was compiled from,
+    /// or `None` if it is not known yet.
+    pub fn cpp_file_name(self) -> Option<&'static str> {
+        match self {
+            FileType::Maplink => Some("data_fld_maplink.cpp"),
+            FileType::MapId => Some("data_fld_mapid.cpp"),
+            FileType::Shop => Some("data_shop.cpp"),
+            FileType::Dispos | FileType::Chr => None,
+            FileType::Lct => Some("data_lct.cpp"),
+        }
+    }
+    
+    /// Name of the main table symbol that every file of this type contains.
+    pub fn anchor_symbol_name(self) -> &'static str {
         match self {
-            FileType::Dispos | FileType::Chr | FileType::Lct => ".data",
-            _ => ".rodata",
+            FileType::Maplink => "datas__Q3_4data3fld7maplink",
+            FileType::MapId => "datas__Q3_4data3fld5mapid",
+            FileType::Shop => "shopList__Q2_4data4shop",
+            FileType::Dispos => "all_disposDataTbl__Q2_4data10DisposData",
+            FileType::Chr => "npcDataTable__Q2_4data3chr",
+            FileType::Lct => "all_lctAnimeDataTbl__Q2_4data3lct",
         }
     }
+    
+    /// Guesses the type of a file based on which anchor symbols it contains.
+    pub fn detect(symbols: &SymbolTable) -> Option<FileType> {
+        FileType::ALL.iter()
+            .copied()
+            .find(|file_type| symbols.contains(file_type.anchor_symbol_name()))
+    }
+    
+    /// Makes sure the file with these symbols is of this type,
+    /// returning a [`FileTypeMismatch`] error otherwise.
+    pub fn ensure_matches(self, symbols: &SymbolTable) -> Result<(), FileTypeMismatch> {
+        if symbols.contains(self.anchor_symbol_name()) {
+            return Ok(());
+        }
+        
+        Err(FileTypeMismatch {
+            expected: self,
+            detected: FileType::detect(symbols),
+        })
+    }
 }
 
 impl Display for FileType {
@@ -63,7 +121,64 @@ impl Display for FileType {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Parses a file type name, ignoring case and accepting a few aliases.
+impl FromStr for FileType {
+    type Err = UnknownFileType;
+    
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string.to_ascii_lowercase().as_str() {
+            "maplink" | "map_link" => Ok(FileType::Maplink),
+            "mapid" | "map_id" => Ok(FileType::MapId),
+            "shop" | "shops" => Ok(FileType::Shop),
+            "dispos" => Ok(FileType::Dispos),
+            "chr" | "chara" => Ok(FileType::Chr),
+            "lct" => Ok(FileType::Lct),
+            _ => Err(UnknownFileType(string.to_owned())),
+        }
+    }
+}
+
+/// Error for a file type name that [`FileType`] could not be parsed from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownFileType(pub String);
+
+impl Display for UnknownFileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown file type {}, expected one of these: {}", self.0, FileType::ALL_VALUES.join(", "))
+    }
+}
+
+impl Error for UnknownFileType {}
+
+/// Error for when a file does not contain the symbols of the requested [`FileType`],
+/// as opposed to a file of the right type that is corrupt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileTypeMismatch {
+    pub expected: FileType,
+    pub detected: Option<FileType>,
+}
+
+impl Display for FileTypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = self.expected;
+        
+        if let Some(detected) = self.detected {
+            write!(f, "File does not contain {expected} anchor symbols; detected type appears to be {detected} \
+                - did you mean --type {detected}?")
+        } else {
+            write!(f, "File does not contain {expected} anchor symbols and does not look like \
+                any supported file type")
+        }
+    }
+}
+
+impl Error for FileTypeMismatch {}
+
+/// Deserialized content of an elf file.
+///
+/// Float fields are compared with their regular `PartialEq`, so a NaN never compares equal
+/// to itself and `0.0` is equal to `-0.0`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum FileData {
     Maplink(Vec<MaplinkArea>),
     MapId(Vec<MapGroup>),
@@ -74,25 +189,161 @@ pub enum FileData {
 }
 
 impl FileData {
-    pub fn cpp_file_name(&self) -> &'static str {
+    /// Parses the yaml representation of a file. Keys not belonging to any field are ignored,
+    /// unless `strict` is set, in which case they are reported as an error.
+    pub fn from_yaml(yaml: &str, strict: bool) -> Result<Self> {
+        if !strict {
+            return Ok(serde_yaml_bw::from_str(yaml)?);
+        }
+        
+        let mut unknown_fields = Vec::new();
+        let data = serde_ignored::deserialize(serde_yaml_bw::Deserializer::from_str(yaml), |path| {
+            unknown_fields.push(path.to_string());
+        })?;
+        
+        ensure!(unknown_fields.is_empty(), "Unknown fields in yaml: {}", unknown_fields.join(", "));
+        Ok(data)
+    }
+    
+    pub fn file_type(&self) -> FileType {
+        match self {
+            FileData::Maplink(_) => FileType::Maplink,
+            FileData::MapId(_) => FileType::MapId,
+            FileData::Shop(_) => FileType::Shop,
+            FileData::Dispos(_) => FileType::Dispos,
+            FileData::Chr(_) => FileType::Chr,
+            FileData::Lct(_) => FileType::Lct,
+        }
+    }
+    
+    pub fn as_maplink(&self) -> Option<&[MaplinkArea]> {
         match self {
-            FileData::Maplink(_) => "data_fld_maplink.cpp",
-            FileData::MapId(_) => "data_fld_mapid.cpp",
-            FileData::Shop(_) => "data_shop.cpp",
-            FileData::Dispos(_) => todo!(),
-            FileData::Chr(_) => todo!(),
-            FileData::Lct(_) => "data_lct.cpp",
+            FileData::Maplink(value) => Some(value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_mapid(&self) -> Option<&[MapGroup]> {
+        match self {
+            FileData::MapId(value) => Some(value),
+            _ => None,
         }
     }
     
+    pub fn as_shop(&self) -> Option<&[Shop]> {
+        match self {
+            FileData::Shop(value) => Some(value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_dispos(&self) -> Option<&[DisposArea]> {
+        match self {
+            FileData::Dispos(value) => Some(value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_chr(&self) -> Option<&ChrData> {
+        match self {
+            FileData::Chr(value) => Some(value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_lct(&self) -> Option<&[AreaLct]> {
+        match self {
+            FileData::Lct(value) => Some(value),
+            _ => None,
+        }
+    }
+    
+    /// Appends the entries of `other` to the entries of this file, failing if they are of different types.
+    ///
+    /// If `dedup_by_id` is set, entries of `other` replace entries with the same id instead
+    /// for formats with unique ids (mapid and dispos).
+    pub fn try_merge(self, other: FileData, dedup_by_id: bool) -> Result<FileData> {
+        let merged = match (self, other) {
+            (FileData::Maplink(mut areas), FileData::Maplink(other)) => {
+                areas.extend(other);
+                FileData::Maplink(areas)
+            },
+            (FileData::MapId(mut groups), FileData::MapId(other)) => {
+                if dedup_by_id {
+                    merge_by_id(&mut groups, other, |group| &group.id);
+                } else {
+                    groups.extend(other);
+                }
+                FileData::MapId(groups)
+            },
+            (FileData::Shop(mut shops), FileData::Shop(other)) => {
+                shops.extend(other);
+                FileData::Shop(shops)
+            },
+            (FileData::Dispos(mut areas), FileData::Dispos(other)) => {
+                if dedup_by_id {
+                    merge_by_id(&mut areas, other, |area| &area.id);
+                } else {
+                    areas.extend(other);
+                }
+                FileData::Dispos(areas)
+            },
+            (FileData::Chr(mut chr), FileData::Chr(other)) => {
+                chr.npc_data.extend(other.npc_data);
+                chr.mobj_data.extend(other.mobj_data);
+                FileData::Chr(chr)
+            },
+            (FileData::Lct(mut lcts), FileData::Lct(other)) => {
+                lcts.extend(other);
+                FileData::Lct(lcts)
+            },
+            (data, other) => {
+                bail!("Cannot merge {} data into {} data", other.file_type(), data.file_type());
+            },
+        };
+        
+        Ok(merged)
+    }
+    
+    /// Makes sure that no two entries share an id that gets turned into a symbol name,
+    /// since the symbols would collide otherwise.
+    pub fn ensure_unique_ids(&self) -> Result<()> {
+        fn ensure_unique<T>(values: &[T], file_type: FileType, id: impl Fn(&T) -> &str) -> Result<()> {
+            let mut seen = HashSet::new();
+            
+            for value in values {
+                let id = id(value);
+                if !seen.insert(id) {
+                    bail!("Duplicate id {id} in {file_type}");
+                }
+            }
+            Ok(())
+        }
+        
+        let file_type = self.file_type();
+        match self {
+            FileData::Maplink(areas) => ensure_unique(areas, file_type, |area| &area.map_name),
+            FileData::MapId(groups) => ensure_unique(groups, file_type, |group| &group.id),
+            FileData::Dispos(areas) => ensure_unique(areas, file_type, |area| &area.id),
+            FileData::Shop(_) | FileData::Chr(_) | FileData::Lct(_) => Ok(()),
+        }
+    }
+    
+    /// See [`FileType::cpp_file_name`].
+    pub fn cpp_file_name(&self) -> Option<&'static str> {
+        self.file_type().cpp_file_name()
+    }
+    
     pub fn heap_category_type(&self) -> ElfCategoryType {
         match self {
-            FileData::Lct(_) => ElfCategoryType::Data,
+            FileData::Chr(_) | FileData::Lct(_) => ElfCategoryType::Data,
             _ => ElfCategoryType::Unit,
         }
     }
     
-    pub fn elf_ident_padding_unk(&self) -> u32 {
+    /// Value of [`ElfHeader::ident_padding_byte`](crate::elf::container::ElfHeader::ident_padding_byte)
+    /// in the original files, since the yaml representation does not contain the elf header.
+    pub fn elf_ident_padding_byte(&self) -> u8 {
         match self {
             FileData::Maplink(_) => 1,
             _ => 0,
@@ -100,6 +351,10 @@ impl FileData {
     }
     
     // this is so confusing
+    /// Offset in the content section after which the original files stop deduplicating strings.
+    ///
+    /// These were found by trial and error, so the fallback for the other formats is a guess.
+    /// It can be overridden using [`ReassembleOptions::string_dedup_size`](crate::ReassembleOptions::string_dedup_size).
     pub fn string_dedup_size(&self) -> u64 {
         match self {
             FileData::Maplink(_) => 0xc32c,
@@ -109,3 +364,63 @@ impl FileData {
         }
     }
 }
+
+/// One line summary of the amount of entries, e.g. `Shop: 5 shops, 43 items`.
+impl Display for FileData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn total<T>(values: &[T], count: impl Fn(&T) -> usize) -> usize {
+            values.iter().map(count).sum()
+        }
+        
+        match self {
+            FileData::Maplink(areas) => write!(f, "Maplink: {} areas, {} links total",
+                areas.len(), total(areas, |area| area.links.len())),
+            FileData::MapId(groups) => write!(f, "MapId: {} groups, {} maps total",
+                groups.len(), total(groups, |group| group.maps.len())),
+            FileData::Shop(shops) => write!(f, "Shop: {} shops, {} items",
+                shops.len(), total(shops, |shop| shop.items.len())),
+            FileData::Dispos(areas) => write!(f, "Dispos: {} areas, {} npcs, {} mobjs, {} items",
+                areas.len(),
+                total(areas, |area| total(&area.map_npcs, |map| map.npcs.len())),
+                total(areas, |area| total(&area.map_mobjs, |map| map.mobjs.len())),
+                total(areas, |area| total(&area.map_items, |map| map.items.len()))),
+            FileData::Chr(chr) => write!(f, "Chr: {} npcs, {} mobjs",
+                chr.npc_data.len(), chr.mobj_data.len()),
+            FileData::Lct(areas) => write!(f, "Lct: {} areas, {} maps, {} lcts",
+                areas.len(),
+                total(areas, |area| area.maps.len()),
+                total(areas, |area| total(&area.maps, |map| map.lcts.len()))),
+        }
+    }
+}
+
+macro_rules! impl_from_for_file_data {
+    ($($variant:ident($type:ty)),* $(,)?) => {
+        $(
+            impl From<$type> for FileData {
+                fn from(value: $type) -> Self {
+                    FileData::$variant(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_file_data! {
+    Maplink(Vec<MaplinkArea>),
+    MapId(Vec<MapGroup>),
+    Shop(Vec<Shop>),
+    Dispos(Vec<DisposArea>),
+    Chr(ChrData),
+    Lct(Vec<AreaLct>),
+}
+
+fn merge_by_id<T>(values: &mut Vec<T>, other: Vec<T>, id: impl Fn(&T) -> &str) {
+    for value in other {
+        if let Some(existing) = values.iter_mut().find(|existing| id(existing) == id(&value)) {
+            *existing = value;
+        } else {
+            values.push(value);
+        }
+    }
+}
@@ -1,28 +1,21 @@
 use std::io::SeekFrom;
 
 use anyhow::Result;
-use byteorder::{BigEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
 use vivibin::{CanRead, CanWriteWithArgs, Readable, Reader, Writable, WriteCtx, scoped_reader_pos};
 
 use crate::{
-    SymbolName,
+    SHOP_ITEMS_SYMBOL_PREFIX, SymbolName,
     binutil::{DataCategory, ElfReadDomain, ElfWriteDomain, NewWriteNullTermiantedSliceArgs, NewWriteStringArgs},
     formats::FileData,
     util::pointer::Pointer,
 };
 
 pub fn read_shops(reader: &mut impl Reader, domain: ElfReadDomain) -> Result<FileData> {
-    let shop_list_len_symbol = domain.find_symbol("shopListLen__Q2_4data4shop")?;
-    reader.seek(SeekFrom::Start(shop_list_len_symbol.offset().into()))?;
-    let shop_list_len = reader.read_u32::<BigEndian>()?;
-    
-    let shop_list_symbol = domain.find_symbol("shopList__Q2_4data4shop")?;
-    reader.seek(SeekFrom::Start(shop_list_symbol.offset().into()))?;
-    
-    let shop_list: Vec<Shop> = (0..shop_list_len)
-        .map(|_| Shop::from_reader(reader, domain))
-        .collect::<Result<_>>()?;
+    let shop_list: Vec<Shop> = domain.read_counted_table(
+        reader, "shopListLen__Q2_4data4shop", "shopList__Q2_4data4shop", false,
+        |reader| Shop::from_reader(reader, domain),
+    )?;
     
     Ok(FileData::Shop(shop_list))
 }
@@ -47,7 +40,7 @@ pub fn write_shops(ctx: &mut impl WriteCtx<DataCategory>, domain: &mut ElfWriteD
     Ok(())
 }
 
-#[derive(Clone, Debug, Writable, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Writable, Deserialize, Serialize)]
 #[extra_write_domain_deps(CanWriteWithArgs<Cat, Option<String>, NewWriteStringArgs>)]
 #[new_serialization]
 pub struct Shop {
@@ -55,31 +48,47 @@ pub struct Shop {
     #[write_args(NewWriteStringArgs::default())]
     pub shop_id: String,
     
+    // unlike maplink and mapid, the item lists are not named after their shop id in the
+    // original file, but are auto-named in order of their offset
     #[write_args(NewWriteNullTermiantedSliceArgs {
-        symbol_name: Some(SymbolName::Internal('s')),
+        symbol_name: Some(SymbolName::Internal(SHOP_ITEMS_SYMBOL_PREFIX)),
         write_length: false,
     })]
     pub items: Vec<SoldItem>,
 }
 
 // TODO: vivibin can't pass along SoldItem's Option<String> dependency
-impl<D: CanRead<String> + CanRead<Option<String>> + CanRead<Pointer>> Readable<D> for Shop {
+impl<D> Readable<D> for Shop
+where
+    D: CanRead<String> + CanRead<Option<String>> + CanRead<Option<Pointer>>,
+{
     fn from_reader_unboxed<R: vivibin::Reader>(reader: &mut R, domain: D) -> Result<Self> {
         let shop_id: String = domain.read(reader)?;
-        let items_ptr: Pointer = domain.read(reader)?;
+        let items_ptr: Option<Pointer> = domain.read(reader)?;
+        
+        // shops without any items may not have an item list at all
+        // (rebuilt files always contain at least the terminating entry though)
+        let Some(items_ptr) = items_ptr else {
+            return Ok(Self { shop_id, items: Vec::new() });
+        };
         
         // TODO: provide abstraction for this
         scoped_reader_pos!(reader);
         reader.seek(SeekFrom::Start(items_ptr.into()))?;
         let mut items = Vec::new();
         loop {
-            let value = SoldItem::from_reader(reader, domain)?;
+            // the list is terminated by an entry consisting of two null pointers,
+            // so check the pointers in the file before parsing the entry
+            let entry_start = reader.stream_position()?;
+            let item_id_ptr: Option<Pointer> = domain.read(reader)?;
+            let requirement_ptr: Option<Pointer> = domain.read(reader)?;
             
-            if value == SoldItem::default() {
+            if item_id_ptr.is_none() && requirement_ptr.is_none() {
                 break;
             }
             
-            items.push(value);
+            reader.seek(SeekFrom::Start(entry_start))?;
+            items.push(SoldItem::from_reader(reader, domain)?);
         }
         
         Ok(Self { shop_id, items })
@@ -5,26 +5,32 @@ use core::{
 };
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail, ensure};
 use binrw::BinWrite;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use indexmap::IndexMap;
-use vivibin::{HeapResolver, HeapToken, WriteCtx, WriteCtxImpl, WriteDomainExt, util::HashMap};
+use log::{trace, warn};
+use vivibin::{HeapResolver, HeapToken, WriteCtx, WriteCtxImpl, WriteDomainExt, align_to, util::HashMap};
 
 use crate::{
-    binutil::{DataCategory, ElfCategoryType, ElfWriteDomain},
+    binutil::{DataCategory, ElfCategoryType, ElfReadDomain, ElfWriteDomain, ReadState},
     elf::{
-        Relocation, Section, Symbol, SymbolHeader, SymbolNameGenerator,
-        container::{ELF_HEADER_IDENT, ElfContainer, ElfHeader},
+        AUTO_SYMBOL_NAME_CHARS, R_PPC_ADDR32, Relocation, Section, SymbolHeader, SymbolNameGenerator,
+        SymbolTable,
+        container::{EF_PPC_EMB, ELF_HEADER_IDENT, EM_PPC, ET_REL, ElfContainer, ElfHeader},
     },
+    error::PaintelfError,
     formats::{
-        FileData, lct::write_lct, mapid::write_mapid, maplink::write_maplink, shop::write_shops,
+        FileData, FileType, chr::{read_chr, write_chr}, dispos::read_dispos, lct::{read_lct, write_lct},
+        mapid::{read_mapid, write_mapid}, maplink::{read_maplink, write_maplink},
+        shop::{read_shops, write_shops},
     },
     util::pointer::Pointer,
 };
 
 pub mod binutil;
 pub mod elf;
+pub mod error;
 pub mod formats;
 pub mod matching;
 pub mod util;
@@ -32,6 +38,31 @@ pub mod util;
 #[cfg(test)]
 mod tests;
 
+/// Tag OR'd onto pointers that were resolved for debugging (see [`link_section_debug`]).
+///
+/// Offsets into the data files never come close to 0x70000000, so the high bits make resolved
+/// pointers stand out from real values when comparing debug output against the original file.
+pub const DEBUG_RELOCATION_TAG: u32 = 0x7000_0000;
+
+// Initial characters of automatically generated symbol names. The rest of the name is a counter
+// per initial character (see `SymbolNameGenerator`), counting up in order of offset. The original
+// files use a separate counter for each kind of data, so these need to match them exactly
+// for the symbol tables to match.
+
+/// Initial character of the symbols of strings.
+pub const STRING_SYMBOL_PREFIX: char = '.';
+/// Initial character of the symbols of boxed values, see [`ElfWriteDomain::box_symbol_char`](binutil::ElfWriteDomain::box_symbol_char).
+pub const BOX_SYMBOL_PREFIX: char = 'l';
+/// Initial character of the symbols of the item lists of shops, which, unlike the lists
+/// in maplink and mapid, are not named after their shop.
+pub const SHOP_ITEMS_SYMBOL_PREFIX: char = 's';
+
+/// Whether `initial_char` can be used for automatically named symbols,
+/// which have to consist of [`AUTO_SYMBOL_NAME_CHARS`] only.
+pub fn is_valid_symbol_prefix(initial_char: char) -> bool {
+    u8::try_from(initial_char).is_ok_and(|c| AUTO_SYMBOL_NAME_CHARS.contains(&c))
+}
+
 #[derive(Clone, Debug)]
 pub enum SymbolName {
     None,
@@ -69,29 +100,276 @@ impl Display for SymbolName {
     }
 }
 
+/// Mangles `name` inside of `namespaces` the way symbols are named in the original files,
+/// e.g. `mangle_name(&["data", "shop"], "shopList")` results in `shopList__Q2_4data4shop`.
+pub fn mangle_name(namespaces: &[&str], name: &str) -> String {
+    let mut result = name.to_string();
+    
+    match namespaces.len() {
+        0 => return result,
+        1 => result.push_str("__"),
+        count => result.push_str(&format!("__Q{count}_")),
+    }
+    
+    for namespace in namespaces {
+        result.push_str(&namespace.len().to_string());
+        result.push_str(namespace);
+    }
+    
+    result
+}
+
+/// Reverses [`mangle_name`], returning the namespaces and the base name of a mangled symbol name.
+/// Returns `None` if `mangled` is not a valid mangled name.
+pub fn demangle_name(mangled: &str) -> Option<(Vec<&str>, &str)> {
+    let (name, mut rest) = mangled.split_once("__")?;
+    
+    let count = if let Some(tail) = rest.strip_prefix('Q') {
+        let (count, tail) = tail.split_once('_')?;
+        rest = tail;
+        count.parse::<usize>().ok()?
+    } else {
+        1
+    };
+    
+    let mut namespaces = Vec::with_capacity(count);
+    
+    for _ in 0..count {
+        let digit_count = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let len: usize = rest[..digit_count].parse().ok()?;
+        
+        namespaces.push(rest.get(digit_count..digit_count + len)?);
+        rest = &rest[digit_count + len..];
+    }
+    
+    rest.is_empty().then_some((namespaces, name))
+}
+
 #[derive(Clone, Debug)]
 pub struct SymbolDeclaration {
     pub name: SymbolName,
     pub offset: HeapToken,
     pub size: u32,
+    /// Heap the symbol is located in, or `None` for the heap it was declared from
+    pub category: Option<DataCategory>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct RelDeclaration {
     pub base_location: usize,
     pub target_location: usize,
+    /// Added to `target_location` while keeping the relocation relative to the symbol
+    /// at `target_location`, even if the result lies within a different symbol
+    pub addend: u32,
+    /// Heap the target is located in, or `None` for the main heap, like [`SymbolDeclaration::category`]
+    pub target_category: Option<DataCategory>,
+}
+
+/// Statistics collected while reassembling an elf file, useful for tuning
+/// a format for byte-exact matching.
+#[derive(Clone, Debug, Default)]
+pub struct ReassembleStats {
+    pub strings_written: usize,
+    pub strings_deduplicated: usize,
+    /// Strings that could not be deduplicated because they were written past the string dedup size
+    pub strings_past_dedup_size: usize,
+    pub symbols: usize,
+    pub auto_named_symbols: usize,
+    pub named_symbols: usize,
+    pub relocations: usize,
+    pub section_sizes: Vec<(String, usize)>,
+    /// Name, resolved offset and size of every emitted symbol, sorted by offset
+    pub symbol_layout: Vec<(String, usize, u32)>,
+}
+
+impl Display for ReassembleStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Strings written: {} ({} deduplicated)", self.strings_written, self.strings_deduplicated)?;
+        if self.strings_past_dedup_size > 0 {
+            writeln!(f, "Duplicate strings written again past the string dedup size: {}", self.strings_past_dedup_size)?;
+        }
+        writeln!(f, "Symbols emitted: {} ({} auto-named, {} named)", self.symbols, self.auto_named_symbols, self.named_symbols)?;
+        writeln!(f, "Relocations emitted: {}", self.relocations)?;
+        writeln!(f, "Section sizes:")?;
+        for (name, size) in &self.section_sizes {
+            writeln!(f, "  {name}: 0x{size:x} bytes")?;
+        }
+        Ok(())
+    }
 }
 
-pub fn reassemble_elf_container(data: &FileData, apply_debug_relocations: bool) -> Result<ElfContainer> {
+/// Reads the bytes of an elf file of type `file_type`.
+pub fn disassemble_bytes(elf_bytes: &[u8], file_type: FileType) -> Result<FileData, PaintelfError> {
+    let (data, _) = disassemble(elf_bytes, file_type)?;
+    Ok(data)
+}
+
+/// Reads the bytes of an elf file of type `file_type`, also returning the parsed container
+/// for access to its symbols and sections.
+pub fn disassemble(elf_bytes: &[u8], file_type: FileType) -> Result<(FileData, ElfContainer), PaintelfError> {
+    let mut reader: Cursor<&[u8]> = Cursor::new(elf_bytes);
+    let elf_file = ElfContainer::from_reader(&mut reader)?;
+    let data = elf_file.read_file_data(file_type)?;
+    
+    Ok((data, elf_file))
+}
+
+/// Rebuilds the bytes of an elf file from its content.
+pub fn reassemble_bytes(data: &FileData) -> Result<Vec<u8>, PaintelfError> {
+    let elf_file = reassemble_elf_container(data, None)?;
+    
+    Ok(elf_file.to_bytes()?)
+}
+
+/// Finds the section containing the data of a file of type `file_type` and sets up
+/// a read domain for it, which keeps track of what is being read in `state`.
+pub fn content_section_and_domain<'a>(
+    elf_file: &'a ElfContainer,
+    file_type: FileType,
+    state: &'a ReadState,
+) -> Result<(&'a Section, ElfReadDomain<'a>)> {
+    let candidates = file_type.content_section_names();
+    let Some(content_section_name) = candidates.iter().find(|name| elf_file.get_section(name).is_some()) else {
+        bail!("Could not find any of the sections {} for {file_type} data; available: {}",
+            candidates.join(", "), elf_file.section_names().join(", "));
+    };
+    
+    section_and_domain(elf_file, content_section_name, state)
+}
+
+/// Like [`content_section_and_domain`], but for the section called `content_section_name`.
+pub fn section_and_domain<'a>(
+    elf_file: &'a ElfContainer,
+    content_section_name: &str,
+    state: &'a ReadState,
+) -> Result<(&'a Section, ElfReadDomain<'a>)> {
+    let content_section = elf_file.require_section(content_section_name)?;
+    let Some(content_relocations) = &content_section.relocations else {
+        return Err(elf_file.missing_section_error(&format!(".rela{content_section_name}")));
+    };
+    
+    let mut domain = ElfReadDomain::new(&content_section.content, content_relocations, &elf_file.symbols, state);
+    
+    // files with their data in .data still keep their strings in .rodata
+    if content_section_name != ".rodata" {
+        let rodata_section = elf_file.require_section(".rodata")?;
+        domain = domain.with_rodata_section(&rodata_section.content);
+    }
+    
+    Ok((content_section, domain))
+}
+
+/// Reads a single table of an elf file without reading the rest of its content, given the name
+/// of the symbol containing the element count and the name of the symbol of the elements themselves.
+///
+/// `read_element` is called once per element, with the reader positioned at the element.
+/// If `trailing_null` is set, the count includes a terminating null element, which is not read.
+pub fn read_single_table<T>(
+    elf_file: &ElfContainer,
+    count_symbol: &str,
+    data_symbol: &str,
+    trailing_null: bool,
+    read_element: impl Fn(&mut Cursor<&[u8]>, ElfReadDomain) -> Result<T>,
+) -> Result<Vec<T>> {
+    let symbol = elf_file.symbols.get(data_symbol)
+        .ok_or_else(|| anyhow!("Could not find symbol {data_symbol}"))?;
+    let section_name = elf_file.section_name_at(symbol.header.st_shndx)
+        .ok_or_else(|| anyhow!("Symbol {data_symbol} is in nonexistent section {}", symbol.header.st_shndx))?;
+    
+    let state = ReadState::default();
+    let (section, domain) = section_and_domain(elf_file, section_name, &state)?;
+    let mut reader: Cursor<&[u8]> = Cursor::new(&section.content);
+    
+    domain.read_counted_table(&mut reader, count_symbol, data_symbol, trailing_null,
+        |reader| read_element(reader, domain))
+}
+
+/// Reads the content of an already parsed elf file of type `file_type`.
+pub fn read_file_data(elf_file: &ElfContainer, file_type: FileType) -> Result<FileData> {
+    file_type.ensure_matches(&elf_file.symbols)?;
+    
+    let state = ReadState::default();
+    let (content_section, domain) = content_section_and_domain(elf_file, file_type, &state)?;
+    
+    let mut reader: Cursor<&[u8]> = Cursor::new(&content_section.content);
+    let data = match file_type {
+        FileType::Maplink => read_maplink(&mut reader, domain),
+        FileType::MapId => read_mapid(&mut reader, domain),
+        FileType::Shop => read_shops(&mut reader, domain),
+        FileType::Dispos => read_dispos(&mut reader, domain),
+        FileType::Chr => read_chr(&mut reader, domain),
+        FileType::Lct => read_lct(&mut reader, domain),
+    }?;
+    
+    let mut expected_ident = ELF_HEADER_IDENT;
+    expected_ident[15] = data.elf_ident_padding_byte();
+    
+    if elf_file.header.e_ident != expected_ident {
+        warn!("Unexpected elf identification {:02x?}, the rebuilt file will not match the original",
+            elf_file.header.e_ident);
+    }
+    
+    Ok(data)
+}
+
+pub fn reassemble_elf_container(data: &FileData, stats: Option<&mut ReassembleStats>) -> Result<ElfContainer> {
+    reassemble_elf_container_with_options(data, ReassembleOptions::default(), stats)
+}
+
+/// Settings for [`reassemble_elf_container_with_options`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReassembleOptions<'a> {
+    /// Also write the target of every pointer into the content, see [`link_section_debug`]
+    pub apply_debug_relocations: bool,
+    /// Overrides [`FileData::string_dedup_size`], the offset after which strings
+    /// are not deduplicated anymore
+    pub string_dedup_size: Option<u64>,
+    /// Copy every content section of this file that is not rebuilt from the data into
+    /// the result unchanged. The type, machine, entry point and flags in the elf header
+    /// are taken from it as well.
+    ///
+    /// Sections with relocations cannot be copied, since their relocations refer to symbols
+    /// of the original file. Symbols inside of copied sections are not kept either.
+    pub template: Option<&'a ElfContainer>,
+    /// Emit symbols with their given names in declaration order instead of matching
+    /// the original files, see [`write_symtab`]
+    pub preserve_symbol_order: bool,
+    /// Never reuse already written strings, to find out whether a mismatch is caused
+    /// by string deduplication. Files rebuilt like this never match the original ones.
+    pub disable_string_dedup: bool,
+    /// Put all strings into a block at the start of `.rodata` instead of right after
+    /// the data that first references them, see [`DataCategory::Strings`]
+    pub leading_strings: bool,
+}
+
+/// Like [`reassemble_elf_container`], but with the settings in `options`.
+pub fn reassemble_elf_container_with_options(
+    data: &FileData,
+    options: ReassembleOptions,
+    stats: Option<&mut ReassembleStats>,
+) -> Result<ElfContainer> {
+    let ReassembleOptions {
+        apply_debug_relocations, string_dedup_size, template, preserve_symbol_order, disable_string_dedup,
+        leading_strings,
+    } = options;
+    let string_dedup_size = string_dedup_size.unwrap_or_else(|| data.string_dedup_size());
+    
+    data.ensure_unique_ids()?;
+    
     let block_offsets;
     
     // serialize data
-    let data_buffer: Option<Vec<u8>>;
-    let rodata_buffer: Option<Vec<u8>>;
+    // (section name, content, relocations originating from that section)
+    let mut content_sections: Vec<(&str, Vec<u8>, Vec<RelDeclaration>)> = Vec::with_capacity(2);
+    
+    let mut domain = ElfWriteDomain::new(string_dedup_size, apply_debug_relocations);
+    domain.deduplicate_strings = !disable_string_dedup;
+    if leading_strings {
+        domain.string_category = DataCategory::Strings;
+    }
     
-    let (mut symbol_declarations, mut relocations) = match data.heap_category_type() {
+    match data.heap_category_type() {
         ElfCategoryType::Unit => {
-            let mut domain = ElfWriteDomain::new(data.string_dedup_size(), apply_debug_relocations);
             let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
             match data {
                 FileData::Maplink(maplink_areas) => {
@@ -103,8 +381,9 @@ pub fn reassemble_elf_container(data: &FileData, apply_debug_relocations: bool)
                 FileData::MapId(map_groups) => {
                     write_mapid(&mut ctx, &mut domain, map_groups)?;
                 },
-                FileData::Dispos(_) => todo!(),
-                FileData::Chr(_) => todo!(),
+                FileData::Dispos(_) => {
+                    bail!(PaintelfError::UnsupportedFileType(data.file_type()));
+                },
                 _ => panic!("Type {data:?} does not use heap category Unit"),
             };
             
@@ -113,23 +392,29 @@ pub fn reassemble_elf_container(data: &FileData, apply_debug_relocations: bool)
             let heap_id = ctx.heap_id_of(DataCategory::Rodata);
             let heap = ctx.heap(&DataCategory::Rodata);
             
+            // leading strings go into the same output buffer, in front of the actual data
+            if let Some(strings_heap) = ctx.heap(&DataCategory::Strings) {
+                resolver.write_heap(&mut domain, heap_id, strings_heap)?;
+                align_to(&mut *resolver.output_buffers[&heap_id].borrow_mut(), 4)?;
+            }
+            
             if let Some(heap) = heap {
                 resolver.write_heap(&mut domain, heap_id, heap)?;
             }
             
-            data_buffer = None;
-            rodata_buffer = Some(mem::take(&mut *resolver.output_buffers[&heap_id].borrow_mut()).into_inner());
+            let rodata_buffer = mem::take(&mut *resolver.output_buffers[&heap_id].borrow_mut()).into_inner();
+            content_sections.push((".rodata", rodata_buffer, mem::take(&mut domain.relocations)));
             block_offsets = resolver.block_offsets;
-            
-            (domain.symbol_declarations, domain.relocations)
         },
         ElfCategoryType::Data => {
-            let mut domain = ElfWriteDomain::new(data.string_dedup_size(), apply_debug_relocations);
             let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Data);
             match data {
                 FileData::Lct(lcts) => {
                     write_lct(&mut ctx, &mut domain, lcts)?;
                 },
+                FileData::Chr(chr) => {
+                    write_chr(&mut ctx, &mut domain, chr)?;
+                },
                 _ => panic!("Type {data:?} does not use heap category Data"),
             };
             
@@ -138,76 +423,189 @@ pub fn reassemble_elf_container(data: &FileData, apply_debug_relocations: bool)
             let data_id = ctx.heap_id_of(DataCategory::Data);
             let rodata_id = ctx.heap_id_of(DataCategory::Rodata);
             
+            // relocations get declared while their heap is written,
+            // so collect them separately for each section
             let data_heap = ctx.heap(&DataCategory::Data);
             if let Some(data_heap) = data_heap {
                 resolver.write_heap(&mut domain, data_id, data_heap)?;
             }
+            let data_relocations = mem::take(&mut domain.relocations);
+            
+            if let Some(strings_heap) = ctx.heap(&DataCategory::Strings) {
+                resolver.write_heap(&mut domain, rodata_id, strings_heap)?;
+                align_to(&mut *resolver.output_buffers[&rodata_id].borrow_mut(), 4)?;
+            }
             
             let rodata_heap = ctx.heap(&DataCategory::Rodata);
             if let Some(rodata_heap) = rodata_heap {
                 resolver.write_heap(&mut domain, rodata_id, rodata_heap)?;
             }
+            let rodata_relocations = mem::take(&mut domain.relocations);
             
             // TODO: improve API of this
-            data_buffer = Some(mem::take(&mut *resolver.output_buffers[&data_id].borrow_mut()).into_inner());
-            rodata_buffer = Some(mem::take(&mut *resolver.output_buffers[&rodata_id].borrow_mut()).into_inner());
+            let data_buffer = mem::take(&mut *resolver.output_buffers[&data_id].borrow_mut()).into_inner();
+            let rodata_buffer = mem::take(&mut *resolver.output_buffers[&rodata_id].borrow_mut()).into_inner();
+            content_sections.push((".data", data_buffer, data_relocations));
+            content_sections.push((".rodata", rodata_buffer, rodata_relocations));
             block_offsets = resolver.block_offsets;
-            
-            (domain.symbol_declarations, domain.relocations)
         },
+    }
+    
+    // pointers to symbols referenced by name point to wherever that symbol ended up
+    // TODO: these are always added to the main section, which is correct as long as
+    // only structs in the main section contain symbol pointers
+    let main_category = data.heap_category_type().main_category();
+    let main_section_name = match main_category {
+        DataCategory::Data => ".data",
+        DataCategory::Rodata | DataCategory::Strings => ".rodata",
     };
     
-    // serialize elf metadata
-    let initial_strtab = format!("\0{}\0", data.cpp_file_name()).into_bytes();
+    // only the offset of a pointer target is known once the heaps are written,
+    // so pointers into other heaps were remembered while writing
+    let pointer_categories: HashMap<usize, DataCategory> = mem::take(&mut domain.pointer_categories).into_iter()
+        .map(|(location, category)| (location.resolve(&block_offsets), category))
+        .collect();
+    if let Some((_, _, relocations)) = content_sections.iter_mut().find(|(section_name, _, _)| *section_name == main_section_name) {
+        for relocation in relocations {
+            relocation.target_category = pointer_categories.get(&relocation.base_location).copied();
+        }
+    }
     
+    for (location, name) in mem::take(&mut domain.symbol_references) {
+        let symbol = domain.symbol_declarations.iter()
+            .find(|symbol| symbol.name.as_str() == Some(name.as_str()))
+            .ok_or_else(|| anyhow!("Pointer refers to symbol {name}, which does not exist in the rebuilt file"))?;
+        
+        let relocation = RelDeclaration {
+            base_location: location.resolve(&block_offsets),
+            target_location: symbol.offset.resolve(&block_offsets),
+            addend: 0,
+            target_category: symbol.category,
+        };
+        
+        let Some((_, content, relocations)) = content_sections.iter_mut().find(|(section_name, _, _)| *section_name == main_section_name) else {
+            bail!("Could not find section {main_section_name} for the pointer to symbol {name}");
+        };
+        
+        if domain.apply_debug_relocations {
+            let value = (relocation.target_location as u32 + relocation.addend) | DEBUG_RELOCATION_TAG;
+            content[relocation.base_location..relocation.base_location + 4].copy_from_slice(&value.to_be_bytes());
+        }
+        relocations.push(relocation);
+    }
+    
+    let content_alignment = domain.content_alignment;
+    let mut symbol_declarations = domain.symbol_declarations;
+    
+    let auto_named_symbol_count = symbol_declarations.iter()
+        .filter(|symbol| symbol.name.is_internal())
+        .count();
+    let named_symbol_count = symbol_declarations.len() - auto_named_symbol_count;
+    
+    // serialize elf metadata
     let mut symbol_indices = HashMap::new();
+    // every content section is followed by its relocation section in the section header table
+    let section_names: Vec<&str> = content_sections.iter().map(|(name, _, _)| *name).collect();
+    let section_index_of = |category: Option<DataCategory>| {
+        let section_name = match category.unwrap_or(main_category) {
+            DataCategory::Data => ".data",
+            DataCategory::Rodata | DataCategory::Strings => ".rodata",
+        };
+        
+        section_names.iter()
+            .position(|name| *name == section_name)
+            .map_or(1, |i| 1 + 2 * i as u16)
+    };
+    
     let (symtab, last_local_symbol, strtab) = write_symtab(
-        initial_strtab,
+        data.cpp_file_name(),
         &block_offsets,
         &mut symbol_indices,
         &mut symbol_declarations,
+        &section_index_of,
+        preserve_symbol_order,
     )?;
-    let rela_rodata = write_relocations(&symbol_indices, &mut relocations)?;
+    
+    let symbol_count = symtab.len() / mem::size_of::<SymbolHeader>();
+    let mut relocation_count = 0;
     
     // populate new ElfContainer
+    let mut e_ident = ELF_HEADER_IDENT;
+    e_ident[15] = data.elf_ident_padding_byte();
+    
+    // values that are not derived from the content are taken from the original file if possible
     // TODO: verify these values are correct in shifted files
     let header = ElfHeader {
-        e_ident: ELF_HEADER_IDENT,
-        e_ident_padding_unk: data.elf_ident_padding_unk(),
-        e_type: 1,
-        e_machine: 0x14,
+        e_ident,
+        e_type: template.map_or(ET_REL, |template| template.header.e_type),
+        e_machine: template.map_or(EM_PPC, |template| template.header.e_machine),
         e_version: 1,
-        e_entry: 0,
+        e_entry: template.map_or(0, |template| template.header.e_entry),
         e_phoff: 0,
         e_shoff: u32::MAX,
-        e_flags: 0x80000000,
+        e_flags: template.map_or(EF_PPC_EMB, |template| template.header.e_flags),
         e_ehsize: 0x34,
         e_phentsize: 0,
         e_phnum: 0,
         e_shentsize: 0x28,
-        e_shnum: 6,
-        e_shstrndx: 3,
+        // overridden by update_section_links
+        e_shnum: 0,
+        e_shstrndx: 0,
     };
     
     let mut result = ElfContainer::new(header);
     
-    if let Some(data_buffer) = data_buffer {
-        result.add_content_section_with_relocations(".data", 4, data_buffer, rela_rodata.clone());
+    for (name, content, mut relocations) in content_sections {
+        let rela = write_relocations(&symbol_indices, &section_index_of, &mut relocations)?;
+        relocation_count += rela.len() / mem::size_of::<Relocation>();
+        
+        result.add_content_section_with_relocations(name, content_alignment, content, rela);
     }
-    if let Some(rodata_buffer) = rodata_buffer {
-        result.add_content_section_with_relocations(".rodata", 4, rodata_buffer, rela_rodata);
+    
+    if let Some(template) = template {
+        copy_extra_sections(&mut result, template)?;
     }
     
-    const SH_STRING_TAB: &[u8] = b"\0.symtab\0.strtab\0.shstrtab\0.rela.rodata\0";
-    result.add_string_table_raw(".shstrtab", 0, 1, SH_STRING_TAB.to_owned());
+    result.add_string_table_raw(".shstrtab", 0, 1, result.section_name_table());
     result.add_symbol_table_raw(".symtab", 0, last_local_symbol, 4, symtab);
     result.add_string_table_raw(".strtab", 0, 1, strtab);
+    result.update_section_links();
+    
+    if let Some(stats) = stats {
+        let mut symbol_layout: Vec<(String, usize, u32)> = symbol_declarations.iter()
+            .map(|symbol| (
+                symbol.name.as_str().unwrap_or("<unnamed>").to_owned(),
+                symbol.offset.resolve(&block_offsets),
+                symbol.size,
+            ))
+            .collect();
+        symbol_layout.sort_by_key(|(_, offset, _)| *offset);
+        
+        *stats = ReassembleStats {
+            strings_written: domain.strings_written,
+            strings_deduplicated: domain.strings_deduplicated,
+            strings_past_dedup_size: domain.strings_past_dedup_size,
+            symbols: symbol_count,
+            auto_named_symbols: auto_named_symbol_count,
+            named_symbols: named_symbol_count,
+            relocations: relocation_count,
+            section_sizes: result.content_sections.values()
+                .chain(result.meta_sections.values())
+                .filter(|section| !section.name.is_empty())
+                .map(|section| (section.name.clone(), section.content.len()))
+                .collect(),
+            symbol_layout,
+        };
+    }
     
     Ok(result)
 }
 
+/// Serializes the relocation section for `relocations`. `symbol_indices` maps the section index
+/// and offset of every symbol to its index, and every relocation has to target one of them.
 pub fn write_relocations(
-    symbol_indices: &HashMap<usize, usize>,
+    symbol_indices: &HashMap<(u16, usize), usize>,
+    section_index_of: impl Fn(Option<DataCategory>) -> u16,
     relocations: &mut [RelDeclaration],
 ) -> Result<Vec<u8>> {
     relocations.sort_by_key(|rel| rel.base_location);
@@ -215,117 +613,62 @@ pub fn write_relocations(
     let mut writer = Cursor::new(Vec::new());
     
     for relocation in relocations {
-        let Some(symbol_idx) = symbol_indices.get(&relocation.target_location) else {
-            eprintln!("WARNING: No symbol at offset 0x{:x} (pointed at from 0x{:x})",
+        let section_index = section_index_of(relocation.target_category);
+        let Some(&symbol_idx) = symbol_indices.get(&(section_index, relocation.target_location)) else {
+            bail!("No symbol at offset 0x{:x} in section {section_index} (pointed at from 0x{:x})",
                 relocation.target_location, relocation.base_location);
-            continue;
         };
         
-        let raw = Relocation::new(relocation.base_location as u32, (symbol_idx << 8 | 1) as u32, 0);
+        let raw = Relocation::with_symbol(relocation.base_location as u32, symbol_idx as u32, R_PPC_ADDR32, relocation.addend);
         raw.write(&mut writer)?;
     }
     
     Ok(writer.into_inner())
 }
 
-pub fn write_symtab(
-    initial_content: Vec<u8>,
-    block_offsets: &[usize],
-    out_symbol_indices: &mut HashMap<usize, usize>,
-    symbol_declarations: &mut Vec<SymbolDeclaration>,
-) -> Result<(Vec<u8>, u32, Vec<u8>)> {
-    // name unnamed internal symbols
-    {
-        let mut symbols: Vec<(char, &mut SymbolDeclaration)> = symbol_declarations.iter_mut()
-            .flat_map(|symbol| match symbol.name {
-                    SymbolName::Internal(initial_char) => Some((initial_char, symbol)),
-                    _ => None,
-            })
-            .collect::<Vec<_>>();
+/// Copies the content sections of `template` that `container` does not have yet.
+fn copy_extra_sections(container: &mut ElfContainer, template: &ElfContainer) -> Result<()> {
+    for section in template.content_sections.values() {
+        if section.name.is_empty() || container.content_sections.contains_key(&section.name) {
+            continue;
+        }
         
-        symbols.sort_by(|(initial_char1, symbol1), (initial_char2, symbol2)| {
-            initial_char1.cmp(initial_char2).then(symbol1.offset.cmp(&symbol2.offset))
-        });
+        ensure!(section.relocations.as_ref().is_none_or(IndexMap::is_empty),
+            "Cannot keep section {} of the original file, because it contains pointers", section.name);
         
-        let mut symbol_name_gen = SymbolNameGenerator::new();
-        let mut prev_initial_char = '\0';
-        
-        for (initial_char, symbol) in symbols {
-            // Make sure every initial_char has its own name gen
-            if prev_initial_char  != initial_char {
-                symbol_name_gen = SymbolNameGenerator::new();
-                prev_initial_char = initial_char;
-            }
-            
-            let tail = symbol_name_gen.next();
-            
-            let mut name = String::with_capacity(tail.len() + 1);
-            name.push(initial_char);
-            name.push_str(tail);
-            
-            symbol.name = SymbolName::InternalUnmangled(name);
+        let symbol_count = template.symbols.iter()
+            .filter(|symbol| symbol.header.st_info != 3
+                && template.section_name_at(symbol.header.st_shndx) == Some(section.name.as_str()))
+            .count();
+        if symbol_count > 0 {
+            warn!("The {symbol_count} symbols in section {} of the original file are not kept", section.name);
         }
-    }
-    
-    // name named internal symbols
-    {
-        let mut symbol_name_gen = SymbolNameGenerator::new();
         
-        let mut symbols: Vec<&mut SymbolDeclaration> = symbol_declarations.iter_mut()
-            .filter(|symbol| matches!(symbol.name, SymbolName::InternalNamed(_)))
-            .collect::<Vec<_>>();
-        
-        symbols.sort_by(|a, b| {
-            let SymbolName::InternalNamed(name1) = &a.name else {
-                unreachable!();
-            };
-            let SymbolName::InternalNamed(name2) = &b.name else {
-                unreachable!();
-            };
-            
-            fn is_less_special(a: &str, b: &str) -> bool {
-                let a_bytes = a.as_bytes();
-                let b_bytes = b.as_bytes();
-                
-                if a_bytes.len() == b_bytes.len() || !a_bytes.starts_with(b_bytes) {
-                    return false;
-                }
-                
-                // SAFETY: Assuming b is valid utf8, it does not end on a continuation byte,
-                // so the first b bytes of a also don't. Therefore, this slice does not start
-                // in the middle of a codepoint and assuming a is valid, this slice is too.
-                let tail = unsafe { str::from_utf8_unchecked(&a_bytes[b_bytes.len()..]) };
-                let first_char = tail.chars().next().unwrap();
-                
-                // wtf??
-                first_char < 'P'
-            }
-            
-            if is_less_special(name1, name2) {
-                Ordering::Less
-            } else if is_less_special(name2, name1) {
-                Ordering::Greater
-            } else {
-                name1.cmp(name2)
-            }
+        container.content_sections.insert(section.name.clone(), Section {
+            relocations: None,
+            ..section.clone()
         });
-        
-        for symbol in symbols {
-            let SymbolName::InternalNamed(name) = &symbol.name else {
-                unreachable!();
-            };
-            
-            let initial_char = name.chars().next().unwrap();
-            let tail = symbol_name_gen.next();
-            
-            let mut result = String::with_capacity(tail.len() + 1);
-            result.push(initial_char);
-            result.push_str(tail);
-            
-            // println!("{name} {result}");
-            
-            symbol.name = SymbolName::InternalUnmangled(result);
-        }
+    }
+    
+    Ok(())
+}
+
+/// Writes the symbol table and string table for `symbol_declarations`, starting with
+/// a FILE symbol named `file_symbol_name` unless it is `None`.
+///
+/// Internal symbols are renamed and sorted like the game's linker does, unless `preserve_symbol_order`
+/// is set, in which case all symbols keep their name and the order they were declared in
+/// (apart from local symbols coming before global ones, which elf requires).
+pub fn write_symtab(
+    file_symbol_name: Option<&str>,
+    block_offsets: &[usize],
+    out_symbol_indices: &mut HashMap<(u16, usize), usize>,
+    symbol_declarations: &mut Vec<SymbolDeclaration>,
+    section_index_of: impl Fn(Option<DataCategory>) -> u16,
+    preserve_symbol_order: bool,
+) -> Result<(Vec<u8>, u32, Vec<u8>)> {
+    if !preserve_symbol_order {
+        assign_internal_symbol_names(symbol_declarations, block_offsets)?;
     }
     
     // start serializing
@@ -334,16 +677,23 @@ pub fn write_symtab(
     
     // null
     BinWrite::write(&SymbolHeader::default(), &mut writer)?;
-    // data_fld_maplink.cpp
-    BinWrite::write(&SymbolHeader {
-        st_name: 1,
-        st_value: 0,
-        st_size: 0,
-        st_info: 4,
-        st_other: 0,
-        st_shndx: 0xFFF1,
-    }, &mut writer)?;
+    symbol_count += 1;
+    
+    // source file, e.g. data_fld_maplink.cpp
+    if file_symbol_name.is_some() {
+        BinWrite::write(&SymbolHeader {
+            st_name: 1,
+            st_value: 0,
+            st_size: 0,
+            st_info: 4,
+            st_other: 0,
+            st_shndx: 0xFFF1,
+        }, &mut writer)?;
+        symbol_count += 1;
+    }
+    
     // .rodata
+    // TODO: emit a section symbol for every content section
     BinWrite::write(&SymbolHeader {
         st_name: 0,
         st_value: 0,
@@ -352,16 +702,22 @@ pub fn write_symtab(
         st_other: 0,
         st_shndx: 1,
     }, &mut writer)?;
-    symbol_count += 3;
+    symbol_count += 1;
     
     // setup serialization of symbols
     let named_symbols: Vec<SymbolDeclaration> = symbol_declarations
         .extract_if(.., |symbol| !symbol.name.is_internal())
         .collect::<Vec<_>>();
     
-    symbol_declarations.sort_by_key(|symbol| symbol.offset.resolve(block_offsets));
+    if !preserve_symbol_order {
+        symbol_declarations.sort_by_key(|symbol| symbol.offset.resolve(block_offsets));
+    }
     
-    let mut strtab = Cursor::new(initial_content);
+    let initial_strtab = match file_symbol_name {
+        Some(file_symbol_name) => format!("\0{file_symbol_name}\0").into_bytes(),
+        None => vec![0],
+    };
+    let mut strtab = Cursor::new(initial_strtab);
     strtab.seek(SeekFrom::End(0))?;
     
     #[allow(clippy::let_with_type_underscore)]
@@ -377,7 +733,7 @@ pub fn write_symtab(
         };
         
         // serialize symbol
-        out_symbol_indices.insert(symbol.offset.resolve(block_offsets), *symbol_count);
+        out_symbol_indices.insert((section_index_of(symbol.category), symbol.offset.resolve(block_offsets)), *symbol_count);
         *symbol_count += 1;
         BinWrite::write(&SymbolHeader {
             st_name: name_ptr,
@@ -385,7 +741,7 @@ pub fn write_symtab(
             st_size: symbol.size,
             st_info,
             st_other: 0,
-            st_shndx: 1,
+            st_shndx: section_index_of(symbol.category),
         }, writer)?;
         
         Ok(())
@@ -400,7 +756,8 @@ pub fn write_symtab(
     let last_local_symbol = symbol_count as u32;
     
     // weird unknown symbols (0x10 implies "external reference" (??))
-    for _ in 0..12 {
+    let unknown_symbol_count = if preserve_symbol_order { 0 } else { 12 };
+    for _ in 0..unknown_symbol_count {
         BinWrite::write(&SymbolHeader {
             st_name: 0,
             st_value: 0,
@@ -413,28 +770,142 @@ pub fn write_symtab(
     
     // serialize named symbols
     for symbol in named_symbols {
-        println!("named symbol {symbol:?}");
+        trace!("Writing named symbol {symbol:?}");
         write_symbol(&mut writer, &mut symbol_count, &symbol, 0x11)?;
     }
     
     Ok((writer.into_inner(), last_local_symbol, strtab.into_inner()))
 }
 
+/// Gives every internal symbol the name the game's linker would have given it.
+///
+/// Symbols declared with [`SymbolName::Internal`] are named after their initial char followed
+/// by a generated tail, counting up separately for each initial char in order of their offset.
+/// Symbols declared with [`SymbolName::InternalNamed`] keep only the first char of their name
+/// and share one generated tail sequence in the order of [`internal_symbol_name_order`].
+pub fn assign_internal_symbol_names(symbol_declarations: &mut [SymbolDeclaration], block_offsets: &[usize]) -> Result<()> {
+    // name unnamed internal symbols
+    {
+        let mut symbols: Vec<(char, &mut SymbolDeclaration)> = symbol_declarations.iter_mut()
+            .flat_map(|symbol| match symbol.name {
+                    SymbolName::Internal(initial_char) => Some((initial_char, symbol)),
+                    _ => None,
+            })
+            .collect::<Vec<_>>();
+        
+        for (initial_char, _) in &symbols {
+            ensure!(is_valid_symbol_prefix(*initial_char), "Invalid initial character {initial_char:?} for automatically named symbols");
+        }
+        
+        symbols.sort_by_key(|(initial_char, symbol)| (*initial_char, symbol.offset.resolve(block_offsets)));
+        
+        let mut symbol_name_gen = SymbolNameGenerator::new();
+        let mut prev_initial_char = '\0';
+        
+        for (initial_char, symbol) in symbols {
+            // Make sure every initial_char has its own name gen
+            if prev_initial_char  != initial_char {
+                symbol_name_gen = SymbolNameGenerator::new();
+                prev_initial_char = initial_char;
+            }
+            
+            let tail = symbol_name_gen.next();
+            
+            let mut name = String::with_capacity(tail.len() + 1);
+            name.push(initial_char);
+            name.push_str(tail);
+            
+            symbol.name = SymbolName::InternalUnmangled(name);
+        }
+    }
+    
+    // name named internal symbols
+    {
+        let mut symbol_name_gen = SymbolNameGenerator::new();
+        
+        let mut symbols: Vec<&mut SymbolDeclaration> = symbol_declarations.iter_mut()
+            .filter(|symbol| matches!(symbol.name, SymbolName::InternalNamed(_)))
+            .collect::<Vec<_>>();
+        
+        symbols.sort_by(|a, b| {
+            let SymbolName::InternalNamed(name1) = &a.name else {
+                unreachable!();
+            };
+            let SymbolName::InternalNamed(name2) = &b.name else {
+                unreachable!();
+            };
+            
+            internal_symbol_name_order(name1, name2)
+        });
+        
+        for symbol in symbols {
+            let SymbolName::InternalNamed(name) = &symbol.name else {
+                unreachable!();
+            };
+            
+            let initial_char = name.chars().next().unwrap();
+            let tail = symbol_name_gen.next();
+            
+            let mut result = String::with_capacity(tail.len() + 1);
+            result.push(initial_char);
+            result.push_str(tail);
+            
+            trace!("Renamed automatically named symbol {name} to {result}");
+            
+            symbol.name = SymbolName::InternalUnmangled(result);
+        }
+    }
+    
+    Ok(())
+}
 
-pub fn link_section_debug(section: &Section, symbols: &IndexMap<String, Symbol>) -> Result<Vec<u8>> {
+/// Order in which [`SymbolName::InternalNamed`] symbols get their names assigned.
+///
+/// This is alphabetical, except that a name extending another name comes first if the char
+/// right after the shared part is below `'P'`, so `"abc0"` comes before `"abc"`, but `"abcx"`
+/// comes after it. The threshold was found by matching the original files, the reason
+/// behind it is unknown.
+pub fn internal_symbol_name_order(name1: &str, name2: &str) -> Ordering {
+    fn is_less_special(a: &str, b: &str) -> bool {
+        let Some(tail) = a.strip_prefix(b) else {
+            return false;
+        };
+        
+        tail.chars().next().is_some_and(|first_char| first_char < 'P')
+    }
+    
+    if is_less_special(name1, name2) {
+        Ordering::Less
+    } else if is_less_special(name2, name1) {
+        Ordering::Greater
+    } else {
+        name1.cmp(name2)
+    }
+}
+
+
+pub fn link_section_debug(section: &Section, symbols: &SymbolTable) -> Result<Vec<u8>> {
     let mut writer: Cursor<Vec<u8>> = Cursor::new(Vec::new());
     
     if let Some(relocations) = section.relocations.as_ref() {
         let mut reader: Cursor<&[u8]> = Cursor::new(&section.content);
         
         while reader.position() < section.content.len() as u64 {
-            if let Some(relocation) = relocations.get(&Pointer::current(&mut reader)?) {
-                let symbol = symbols.get_index((relocation.info >> 8) as usize)
-                    .ok_or_else(|| anyhow!("Could not find symbol at index {}", relocation.info >> 8))?
-                    .1;
+            let offset = Pointer::current(&mut reader)?;
+            
+            if let Some(relocation) = relocations.get(&offset) {
+                ensure!(relocation.reloc_type() == R_PPC_ADDR32, "Unsupported relocation type {} at 0x{:x}",
+                    relocation.reloc_type(), offset.0);
+                
+                let symbol = symbols.get_index(relocation.symbol_index() as usize)
+                    .ok_or_else(|| anyhow!("Could not find symbol at index {}", relocation.symbol_index()))?;
                 
-                writer.write_u32::<BigEndian>(symbol.offset() | 0x70000000)?;
-                assert_eq!(reader.read_u32::<BigEndian>()?, 0);
+                let target = symbol.offset().checked_add(relocation.addend)
+                    .ok_or_else(|| anyhow!("Relocation to symbol {} with addend 0x{:x} overflows (at 0x{:x})",
+                        symbol.name, relocation.addend, offset.0))?;
+                writer.write_u32::<BigEndian>(target | DEBUG_RELOCATION_TAG)?;
+                let real_value = reader.read_u32::<BigEndian>()?;
+                ensure!(real_value == 0, "Expected relocated pointer at 0x{:x} to be 0, got 0x{real_value:x}", offset.0);
             } else {
                 let mut word: [u8; 4] = Default::default();
                 let bytes_read = reader.read(&mut word)?;
@@ -1,23 +1,23 @@
 use std::{
-    env, fs,
+    env,
+    fs,
     io::Cursor,
     panic,
     path::{Path, PathBuf},
     process::exit,
 };
 
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Result, anyhow, bail, ensure};
 use indoc::printdoc;
+use log::{LevelFilter, info};
 use paintelf::{
-    binutil::ElfReadDomain,
-    elf::{Section, container::ElfContainer},
-    formats::{
-        FileData, FileType, chr::read_chr, dispos::read_dispos, lct::read_lct, mapid::read_mapid,
-        maplink::read_maplink, shop::read_shops,
-    },
+    ReassembleOptions, ReassembleStats,
+    elf::{Section, container::{EF_PPC_EMB, ELF_HEADER_IDENT, EM_PPC, ET_REL, ElfContainer}},
+    formats::{FileData, FileType},
     link_section_debug,
     matching::{test_reserialize_directly, test_reserialize_from_content},
-    reassemble_elf_container,
+    reassemble_elf_container_with_options,
+    util::{write_atomic, yaml::compact_yaml, yaz0},
 };
 
 fn main() -> Result<()> {
@@ -32,7 +32,20 @@ fn main() -> Result<()> {
     
     let mut help = false;
     let mut is_debug = false;
+    let mut show_stats = false;
+    let mut show_symbol_layout = false;
+    let mut compact = false;
+    let mut verbosity = 0;
+    let mut list_types = false;
+    let mut compress_yaz0 = false;
+    let mut size_only = false;
+    let mut strict = false;
+    let mut no_dedup = false;
+    let mut show_header = false;
+    let mut string_dedup_size = None;
     let mut file_type = None;
+    let mut dump_section_name = None;
+    let mut template_path = None;
     let mut positional = Vec::new();
     
     while let Some(arg) = argv.next() {
@@ -43,6 +56,36 @@ fn main() -> Result<()> {
             "-d" | "--debug" => {
                 is_debug = true;
             },
+            "-s" | "--stats" => {
+                show_stats = true;
+            },
+            "--symbol-layout" => {
+                show_symbol_layout = true;
+            },
+            "-c" | "--compact" => {
+                compact = true;
+            },
+            "-v" | "--verbose" => {
+                verbosity += 1;
+            },
+            "-vv" => {
+                verbosity += 2;
+            },
+            "--list-types" => {
+                list_types = true;
+            },
+            "--size-only" => {
+                size_only = true;
+            },
+            "--strict" => {
+                strict = true;
+            },
+            "--no-dedup" => {
+                no_dedup = true;
+            },
+            "--header" => {
+                show_header = true;
+            },
             "-t" | "--type" => {
                 if file_type.is_some() {
                     bail!("Cannot define --type argument twice");
@@ -50,11 +93,38 @@ fn main() -> Result<()> {
                 
                 let name = argv.next()
                     .ok_or_else(|| anyhow!("Expected one of these file types: {}", FileType::ALL_VALUES.join(", ")))?;
-                file_type = FileType::from_string(&name);
+                file_type = Some(name.parse::<FileType>()?);
+            },
+            "--compress" => {
+                let format = argv.next()
+                    .ok_or_else(|| anyhow!("Expected compression format after --compress (supported: yaz0)"))?;
                 
-                if file_type.is_none() {
-                    bail!("Unknown file type {name}, expected one of these: {}", FileType::ALL_VALUES.join(", "));
+                if !format.eq_ignore_ascii_case("yaz0") {
+                    bail!("Unknown compression format {format}, only yaz0 is supported");
                 }
+                compress_yaz0 = true;
+            },
+            "--string-dedup-size" => {
+                let value = argv.next()
+                    .ok_or_else(|| anyhow!("Expected offset after --string-dedup-size"))?;
+                let digits = value.strip_prefix("0x").unwrap_or(&value);
+                
+                string_dedup_size = Some(u64::from_str_radix(digits, 16)
+                    .map_err(|_| anyhow!("Invalid hexadecimal offset {value}"))?);
+            },
+            "--template" => {
+                let path = argv.next()
+                    .ok_or_else(|| anyhow!("Expected path to the original elf file after --template"))?;
+                template_path = Some(PathBuf::from(path));
+            },
+            "--dump-section" => {
+                if dump_section_name.is_some() {
+                    bail!("Cannot define --dump-section argument twice");
+                }
+                
+                let name = argv.next()
+                    .ok_or_else(|| anyhow!("Expected section name after --dump-section"))?;
+                dump_section_name = Some(name);
             },
             _ => {
                 positional.push(arg);
@@ -62,13 +132,47 @@ fn main() -> Result<()> {
         }
     }
     
+    // --debug output is logged at info level, so keep showing it without -v
+    let log_level = match verbosity {
+        0 if is_debug => LevelFilter::Info,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(log_level)
+        .format_timestamp(None)
+        .format_target(false)
+        .parse_default_env()
+        .init();
+    
+    if list_types {
+        print_file_types();
+        return Ok(());
+    }
+    
     if positional.len() != 2 || help {
         printdoc!("
-            Usage: paintelf [options] <path to decompressed .elf>
+            Usage: paintelf [options] <path to decompressed .elf or directory of .elf files>
             
             Options:
               -h | --help: Shows this text.
-              -t | --type <{}>: Type of the elf file
+              -t | --type <{}>: Type of the elf file (detected automatically if not given)
+              -s | --stats: Print statistics about the rebuilt elf file
+              --symbol-layout: Print the offset of every symbol in the rebuilt elf file
+              -v | -vv | --verbose: Print more information about what is happening (can be repeated)
+              -c | --compact: Write structs with only simple values on a single line in the yaml output
+              --dump-section <name>: Write the raw content of a section (e.g. .rodata) into a file
+              --header: Print the ELF header of the file
+              --list-types: Print every file type together with its content section and source file
+              --string-dedup-size <hex offset>: Override after which offset strings are not deduplicated anymore
+              --no-dedup: Don't deduplicate strings at all (the result won't match the original file,
+                  but this helps finding out whether a mismatch is caused by string deduplication)
+              --compress yaz0: Compress the rebuilt elf file (Yaz0 compressed input is detected automatically)
+              --template <original .elf>: Keep the header flags and the sections of the original file that are not rebuilt
+              --size-only: Print the size of the rebuilt elf file and its sections instead of writing it
+              --strict: Reject unknown fields in the yaml file instead of ignoring them (catches typos)
             
             (Supported elf files are: data_fld_maplink.elf, data_shop.elf)
             ",
@@ -80,28 +184,88 @@ fn main() -> Result<()> {
     let input_file_path_str = positional[1].as_str();
     let input_file_path = PathBuf::from(input_file_path_str);
     
-    if input_file_path_str.ends_with(".yaml") {
-        reassemble_elf(&input_file_path, is_debug)
+    if let Some(section_name) = dump_section_name {
+        return dump_section(&input_file_path, &section_name);
+    }
+    
+    if show_header {
+        return print_header(&input_file_path);
+    }
+    
+    if input_file_path.is_dir() {
+        disassemble_directory(&input_file_path, file_type, compact)
+    } else if input_file_path_str.ends_with(".yaml") {
+        reassemble_elf(&input_file_path, file_type, is_debug, show_stats, show_symbol_layout, compress_yaz0,
+            string_dedup_size, no_dedup, size_only, strict, template_path.as_deref())
     } else {
-        let Some(file_type) = file_type else {
-            bail!("Expected one of these file types to be passed with '--type' argument: {}",
-                FileType::ALL_VALUES.join(", "));
-        };
-        
-        disassemble_elf(&input_file_path, file_type, is_debug)
+        disassemble_elf(&input_file_path, file_type, is_debug, compact)?;
+        Ok(())
     }
 }
 
-fn reassemble_elf(input_file_path: &Path, is_debug: bool) -> Result<()> {
+fn print_file_types() {
+    println!("{:<10}{:<10}{}", "Type", "Section", "Source file");
+    for file_type in FileType::ALL {
+        println!("{:<10}{:<10}{}", file_type.to_string(), file_type.content_section_name(),
+            file_type.cpp_file_name().unwrap_or("(unsupported)"));
+    }
+}
+
+/// Reads an elf file, decompressing it first if it is Yaz0 compressed.
+fn read_elf_bytes(input_file_path: &Path) -> Result<Vec<u8>> {
+    let bytes = fs::read(input_file_path)?;
+    
+    if yaz0::is_yaz0(&bytes) {
+        yaz0::decompress(&bytes)
+    } else {
+        Ok(bytes)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reassemble_elf(
+    input_file_path: &Path, file_type: Option<FileType>, is_debug: bool, show_stats: bool, show_symbol_layout: bool,
+    compress_yaz0: bool, string_dedup_size: Option<u64>, no_dedup: bool, size_only: bool, strict: bool,
+    template_path: Option<&Path>,
+) -> Result<()> {
     let input_file = fs::read_to_string(input_file_path)?;
-    let data: FileData = serde_yaml_bw::from_str(&input_file)?;
+    let data = FileData::from_yaml(&input_file, strict)?;
+    
+    // the yaml already says which type it is, but catch scripts passing the wrong file
+    if let Some(file_type) = file_type {
+        ensure!(data.file_type() == file_type, "YAML contains {} data but --type {file_type} was given", data.file_type());
+    }
     
-    if matches!(data, FileData::Dispos(_) | FileData::Chr(_)) && !is_debug {
+    if matches!(data, FileData::Dispos(_)) && !is_debug {
         eprintln!("Rebuilding data_dispos.elf is not supported yet!");
         exit(1);
     }
     
-    let out_elf = reassemble_elf_container(&data, false)?;
+    let mut stats = ReassembleStats::default();
+    let collect_stats = show_stats || show_symbol_layout;
+    let template = match template_path {
+        Some(template_path) => Some(ElfContainer::from_reader(&mut Cursor::new(read_elf_bytes(template_path)?))?),
+        None => None,
+    };
+    
+    let options = ReassembleOptions {
+        string_dedup_size,
+        template: template.as_ref(),
+        disable_string_dedup: no_dedup,
+        ..Default::default()
+    };
+    let out_elf = reassemble_elf_container_with_options(&data, options, collect_stats.then_some(&mut stats))?;
+    
+    if show_stats {
+        print!("{stats}");
+    }
+    
+    if show_symbol_layout {
+        println!("Symbol layout:");
+        for (name, offset, size) in &stats.symbol_layout {
+            println!("  0x{offset:08x} (0x{size:x} bytes): {name}");
+        }
+    }
     
     // write resulting elf
     let mut base_name = input_file_path.file_stem()
@@ -110,45 +274,117 @@ fn reassemble_elf(input_file_path: &Path, is_debug: bool) -> Result<()> {
     base_name.push("_modified.elf");
     let out_path = input_file_path.with_file_name(base_name);
     
-    fs::write(&out_path, &out_elf.to_bytes()?)?;
+    let mut out_bytes = out_elf.to_bytes()?;
+    
+    if size_only {
+        print_sizes(&out_elf, out_bytes.len(), compress_yaz0.then(|| yaz0::compress(&out_bytes).len()));
+        return Ok(());
+    }
+    
+    if compress_yaz0 {
+        out_bytes = yaz0::compress(&out_bytes);
+    }
+    
+    write_atomic(&out_path, out_bytes)?;
     
     Ok(())
 }
 
-fn disassemble_elf(input_file_path: &Path, file_type: FileType, is_debug: bool) -> Result<()> {
-    let elf_file_raw = fs::read(input_file_path)?;
-    let mut reader: Cursor<&[u8]> = Cursor::new(&elf_file_raw);
+fn print_sizes(elf_file: &ElfContainer, total_size: usize, compressed_size: Option<usize>) {
+    println!("Total size: 0x{total_size:x} bytes ({total_size} bytes)");
+    if let Some(compressed_size) = compressed_size {
+        println!("Yaz0 compressed: 0x{compressed_size:x} bytes ({compressed_size} bytes)");
+    }
     
-    let elf_file = ElfContainer::from_reader(&mut reader)?;
+    println!("Sections:");
+    for (name, size) in elf_file.section_sizes() {
+        println!("  {name:<14}0x{size:x} bytes");
+    }
+}
+
+fn dump_section(input_file_path: &Path, section_name: &str) -> Result<()> {
+    let elf_file_raw = read_elf_bytes(input_file_path)?;
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&elf_file_raw[..]))?;
     
-    // get necessary sections
-    let content_section = &elf_file.content_sections[file_type.content_section_name()];
-    let Some(content_relocations) = &content_section.relocations else {
-        bail!("Could not find section .rela{}", file_type.content_section_name());
-    };
+    let section = elf_file.require_section(section_name)?;
     
-    let rodata_section = match file_type.content_section_name() {
-        ".rodata" => content_section,
-        _ => &elf_file.content_sections[".rodata"],
-    };
+    let extension = format!("{}.bin", section_name.strip_prefix(".").unwrap_or(section_name));
+    let out_path = input_file_path.with_extension(extension);
+    write_atomic(&out_path, &section.content)?;
     
-    // parse maplink file
-    let domain = ElfReadDomain::new(&rodata_section.content, content_relocations, &elf_file.symbols);
-    
-    let mut reader: Cursor<&[u8]> = Cursor::new(&content_section.content);
-    let maplink = match file_type {
-        FileType::Maplink => read_maplink(&mut reader, domain)?,
-        FileType::MapId => read_mapid(&mut reader, domain)?,
-        FileType::Shop => read_shops(&mut reader, domain)?,
-        FileType::Dispos => read_dispos(&mut reader, domain)?,
-        FileType::Chr => read_chr(&mut reader, domain)?,
-        FileType::Lct => read_lct(&mut reader, domain)?,
-    };
+    println!("Wrote raw content of section '{section_name}' to {}", out_path.display());
+    Ok(())
+}
+
+fn print_header(input_file_path: &Path) -> Result<()> {
+    let elf_file_raw = read_elf_bytes(input_file_path)?;
+    let header = ElfContainer::from_reader(&mut Cursor::new(&elf_file_raw[..]))?.header;
+    
+    // flag the values that differ from what paintelf writes
+    let expected = |matches: bool| if matches { "" } else { " (unexpected)" };
     
-    let yaml = serde_yaml_bw::to_string(&maplink)?;
+    let is_big_endian = header.e_ident[5] == ELF_HEADER_IDENT[5];
+    println!("Byte order:    {}{}", if is_big_endian { "big endian" } else { "little endian" }, expected(is_big_endian));
+    println!("Type:          0x{:x}{}", header.e_type, expected(header.e_type == ET_REL));
+    println!("Machine:       0x{:x}{}", header.e_machine, expected(header.e_machine == EM_PPC));
+    println!("Flags:         0x{:x}{}", header.e_flags, expected(header.e_flags == EF_PPC_EMB));
+    println!("Entry:         0x{:x}", header.e_entry);
+    println!("Sections:      {}", header.e_shnum);
+    println!("Section names: section {}", header.e_shstrndx);
+    println!("Header table:  0x{:x}", header.e_shoff);
+    println!("Ident padding: {}", header.ident_padding_byte());
+    Ok(())
+}
+
+/// Disassembles every `.elf` file in a directory, continuing past files that fail
+/// and exiting with an error code at the end if any of them did.
+fn disassemble_directory(dir_path: &Path, file_type: Option<FileType>, compact: bool) -> Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir_path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("elf")));
+    paths.sort();
+    
+    let mut failed = 0;
+    
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        
+        match disassemble_elf(path, file_type, false, compact) {
+            Ok(file_type) => println!("OK      {name} ({file_type})"),
+            Err(err) => {
+                println!("FAILED  {name}: {err}");
+                failed += 1;
+            },
+        }
+    }
+    
+    println!("Disassembled {} of {} files", paths.len() - failed, paths.len());
+    
+    if failed > 0 {
+        exit(1);
+    }
+    Ok(())
+}
+
+/// Disassembles an elf file into yaml, detecting its type from its symbols if `file_type` is `None`.
+fn disassemble_elf(input_file_path: &Path, file_type: Option<FileType>, is_debug: bool, compact: bool) -> Result<FileType> {
+    let elf_file_raw = read_elf_bytes(input_file_path)?;
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&elf_file_raw[..]))?;
+    
+    let file_type = file_type.or_else(|| FileType::detect(&elf_file.symbols))
+        .ok_or_else(|| anyhow!("Could not detect the type of {}, expected one of these file types \
+            to be passed with '--type' argument: {}", input_file_path.display(), FileType::ALL_VALUES.join(", ")))?;
+    let data = elf_file.read_file_data(file_type)?;
+    
+    let mut yaml = serde_yaml_bw::to_string(&data)?;
+    
+    if compact {
+        yaml = compact_yaml(&yaml);
+    }
     
     let out_path = input_file_path.with_extension("yaml");
-    fs::write(out_path, yaml)?;
+    write_atomic(&out_path, yaml)?;
     
     // debug features to facilitate matching re-serializing
     if is_debug {
@@ -156,8 +392,8 @@ fn disassemble_elf(input_file_path: &Path, file_type: FileType, is_debug: bool)
         let write_section_debug = |section: &Section| -> Result<()> {
             let out_section: Vec<u8> = link_section_debug(section, &elf_file.symbols)?;
             let out_path = input_file_path.with_extension(section.name.strip_prefix(".").unwrap_or(&section.name));
-            fs::write(out_path, &out_section)?;
-            println!("[debug] Wrote section '{}' with potential relocations applied", section.name);
+            write_atomic(&out_path, &out_section)?;
+            info!("Wrote section '{}' with potential relocations applied", section.name);
             Ok(())
         };
         
@@ -176,8 +412,8 @@ fn disassemble_elf(input_file_path: &Path, file_type: FileType, is_debug: bool)
         test_reserialize_directly(input_file_path, true, &elf_file_raw, &elf_file)?;
         
         // try re-serializing elf file from just content
-        test_reserialize_from_content(input_file_path, true, &elf_file, &elf_file_raw, &maplink)?;
+        test_reserialize_from_content(input_file_path, true, &elf_file, &elf_file_raw, &data)?;
     }
     
-    Ok(())
+    Ok(file_type)
 }
@@ -1,11 +1,13 @@
-use std::{fs, path::Path};
+use std::path::Path;
 
 use anyhow::{anyhow, bail, Result};
+use log::info;
 
 use crate::{
     elf::{Section, container::ElfContainer},
     formats::FileData,
-    link_section_debug, reassemble_elf_container,
+    link_section_debug, reassemble_elf_container, reassemble_elf_container_with_options, ReassembleOptions,
+    util::write_atomic,
 };
 
 pub fn test_reserialize_directly(input_file_path: &Path, output_file: bool, original: &[u8], deserialized: &ElfContainer) -> Result<()> {
@@ -14,8 +16,8 @@ pub fn test_reserialize_directly(input_file_path: &Path, output_file: bool, orig
     if output_file {
         let out_path = input_file_path.with_extension("elf2");
         
-        fs::write(&out_path, &out_elf)?;
-        println!("[debug] Directly re-serialized elf file to {}", out_path.file_name().unwrap().display());
+        write_atomic(&out_path, &out_elf)?;
+        info!("Directly re-serialized elf file to {}", out_path.file_name().unwrap().display());
     }
     
     assert_eq!(original, out_elf, "Directly re-serialized elf does not match");
@@ -26,7 +28,8 @@ pub fn test_reserialize_directly(input_file_path: &Path, output_file: bool, orig
 pub fn test_reserialize_from_content(input_file_path: &Path, output_file: bool, original: &ElfContainer, original_bytes: &[u8], deserialized: &FileData) -> Result<()> {
     // test all sections for matching directly
     // (apply relocations directly into section content to make this easier)
-    let debug_elf = reassemble_elf_container(deserialized, true)?;
+    let options = ReassembleOptions { apply_debug_relocations: true, ..Default::default() };
+    let debug_elf = reassemble_elf_container_with_options(deserialized, options, None)?;
     
     let mut base_name = input_file_path.file_stem()
         .ok_or_else(|| anyhow!("Invalid file path {}", input_file_path.display()))?
@@ -42,8 +45,8 @@ pub fn test_reserialize_from_content(input_file_path: &Path, output_file: bool,
         
         if output_file {
             out_path.set_extension(name);
-            fs::write(&out_path, &section.content)?;
-            println!("[debug] Wrote re-serialized section '{}' with potential relocations applied", section.name);
+            write_atomic(&out_path, &section.content)?;
+            info!("Wrote re-serialized section '{}' with potential relocations applied", section.name);
         }
         
         Ok(())
@@ -56,10 +59,11 @@ pub fn test_reserialize_from_content(input_file_path: &Path, output_file: bool,
         
         let original_content = link_section_debug(original_section, &original.symbols)?;
         
-        assert!(original_content == section.content, "Re-serialized section '{}' does not match", section.name);
+        assert!(original_content == section.content, "Re-serialized section '{}' does not match: {}",
+            section.name, describe_mismatch(original, &section.name, &original_content, &section.content));
         
         if !output_file {
-            println!("Section '{}' matches", section.name)
+            info!("Section '{}' matches", section.name);
         }
         Ok(())
     };
@@ -87,16 +91,41 @@ pub fn test_reserialize_from_content(input_file_path: &Path, output_file: bool,
     }
     
     // test the entire elf file for matching
-    let final_elf = reassemble_elf_container(deserialized, false)?;
+    let final_elf = reassemble_elf_container(deserialized, None)?;
     let final_elf_bytes = final_elf.to_bytes()?;
     
     if output_file {
         out_path.set_extension("elf");
-        fs::write(&out_path, &final_elf_bytes)?;
-        println!("[debug] Re-serialized elf file to {}", out_path.file_name().unwrap().display());
+        write_atomic(&out_path, &final_elf_bytes)?;
+        info!("Re-serialized elf file to {}", out_path.file_name().unwrap().display());
     }
     
     assert!(original_bytes == final_elf_bytes, "Re-serialized elf file does not match");
     
     Ok(())
 }
+
+/// Describes the first byte where `original_content` and `content` of section `section_name` differ,
+/// including the innermost symbol of the original file containing it.
+pub fn describe_mismatch(original: &ElfContainer, section_name: &str, original_content: &[u8], content: &[u8]) -> String {
+    let offset = original_content.iter().zip(content)
+        .position(|(a, b)| a != b)
+        .unwrap_or(original_content.len().min(content.len()));
+    let mut description = format!("mismatch at 0x{offset:x}");
+    
+    if original_content.len() != content.len() {
+        description.push_str(&format!(" (size 0x{:x} instead of 0x{:x})", content.len(), original_content.len()));
+    }
+    
+    let section_index = original.section_index_of(section_name);
+    let symbol = original.symbols.symbols_at_offset(offset as u32).into_iter()
+        .filter(|symbol| Some(symbol.header.st_shndx) == section_index)
+        .min_by_key(|symbol| symbol.size());
+    
+    if let Some(symbol) = symbol {
+        let name = if symbol.name.is_empty() { "<unnamed>" } else { &symbol.name };
+        description.push_str(&format!(", within symbol {name} (field offset +0x{:x})", offset as u32 - symbol.offset()));
+    }
+    
+    description
+}
@@ -1,12 +1,43 @@
-use std::{ffi::OsStr, fs, io::Cursor, path::Path};
+use core::cmp::Ordering;
+use std::{env, ffi::OsStr, fs, io::Cursor, panic, path::Path};
 
 use anyhow::Result;
+use binrw::{BinRead, BinWrite, meta::{ReadEndian, WriteEndian}};
+use indoc::indoc;
+use serde::{Deserialize, Serialize};
+use vivibin::{
+    CanRead, CanWrite, HeapResolver, ReadDomain, Readable, Writable, WriteCtx, WriteCtxImpl, WriteDomainExt,
+    util::HashMap,
+};
 
 use crate::{
-    binutil::ElfReadDomain,
-    elf::container::ElfContainer,
-    formats::{mapid::read_mapid, maplink::read_maplink, shop::read_shops, FileData},
-    matching::{test_reserialize_directly, test_reserialize_from_content},
+    binutil::{
+        DataCategory, ElfReadDomain, ElfWriteDomain, ReadState, WriteStringArgs, WriteSymbolArgs,
+        check_count_matches_symbol,
+    },
+    elf::{
+        container::{ElfContainer, ElfHeader, EF_PPC_EMB, ELF_HEADER_IDENT, EM_PPC, ET_REL},
+        Relocation, Section, SectionHeader, SectionType, Symbol, SymbolHeader, SymbolNameGenerator,
+        SymbolTable, AUTO_SYMBOL_NAME_CHAR_COUNT, R_PPC_ADDR32,
+    },
+    formats::{
+        chr::{ChrData, MobjDef, MobjDefPtr, ModelList, NpcDef, NpcDefPtr},
+        lct::{read_lct, LctKind},
+        dispos::{DisposArea, DisposNpc, Npc},
+        mapid::{read_mapid, MapDefinition, MapGroup},
+        maplink::{read_maplink, Link, MAPLINK_AREA_SIZE},
+        shop::{read_shops, Shop, SoldItem},
+        FileData, FileType, FileTypeMismatch, UnknownFileType,
+    },
+    matching::{describe_mismatch, test_reserialize_directly, test_reserialize_from_content},
+    assign_internal_symbol_names, content_section_and_domain, demangle_name, disassemble, disassemble_bytes,
+    link_section_debug, mangle_name, reassemble_bytes, reassemble_elf_container,
+    reassemble_elf_container_with_options, ReassembleOptions,
+    internal_symbol_name_order, is_valid_symbol_prefix, read_single_table, write_relocations, write_symtab,
+    ReassembleStats, RelDeclaration, SymbolDeclaration, SymbolName,
+    BOX_SYMBOL_PREFIX, DEBUG_RELOCATION_TAG, SHOP_ITEMS_SYMBOL_PREFIX, STRING_SYMBOL_PREFIX,
+    error::PaintelfError,
+    util::{edit_distance, pointer::{Pointer, SymbolPointer}, read_string, write_atomic, yaml::compact_yaml, yaz0},
 };
 
 fn reserialize_any_directly<S: AsRef<OsStr> + ?Sized>(path: &S) {
@@ -25,10 +56,11 @@ fn reserialize_any_directly<S: AsRef<OsStr> + ?Sized>(path: &S) {
     test_reserialize_directly(path, false, &input_file, &elf_file).unwrap();
 }
 
-fn reserialize_any_from_content<S: AsRef<OsStr> + ?Sized>(
+fn read_any_from_content<S: AsRef<OsStr> + ?Sized>(
     path: &S,
+    file_type: FileType,
     content_callback: impl FnOnce(&mut Cursor<&[u8]>, ElfReadDomain) -> Result<FileData>,
-) {
+) -> (Vec<u8>, ElfContainer, FileData) {
     let path = Path::new(path);
     
     if !path.is_file() {
@@ -40,24 +72,24 @@ fn reserialize_any_from_content<S: AsRef<OsStr> + ?Sized>(
     
     let elf_file = ElfContainer::from_reader(&mut reader).unwrap();
     
-    // get necessary sections
-    let rodata_section = &elf_file.content_sections[".rodata"];
-    let Some(rodata_relocations) = &rodata_section.relocations else {
-        panic!("Could not find section .rela.rodata");
-    };
-    
-    // parse maplink file
-    let domain = ElfReadDomain::new(
-        &rodata_section.content,
-        rodata_relocations,
-        &elf_file.symbols,
-    );
+    let state = ReadState::default();
+    let (content_section, domain) = content_section_and_domain(&elf_file, file_type, &state).unwrap();
     
-    let mut reader: Cursor<&[u8]> = Cursor::new(&rodata_section.content);
+    let mut reader: Cursor<&[u8]> = Cursor::new(&content_section.content);
     let data = content_callback(&mut reader, domain).unwrap();
     
+    (input_file, elf_file, data)
+}
+
+fn reserialize_any_from_content<S: AsRef<OsStr> + ?Sized>(
+    path: &S,
+    file_type: FileType,
+    content_callback: impl FnOnce(&mut Cursor<&[u8]>, ElfReadDomain) -> Result<FileData>,
+) {
+    let (input_file, elf_file, data) = read_any_from_content(path, file_type, content_callback);
+    
     println!("Attempting to re-serialize data from content");
-    test_reserialize_from_content(path, false, &elf_file, &input_file, &data).unwrap();
+    test_reserialize_from_content(Path::new(path), false, &elf_file, &input_file, &data).unwrap();
 }
 
 #[test]
@@ -67,9 +99,360 @@ fn reserialize_maplink_directly() {
 
 #[test]
 fn reserialize_maplink_from_content() {
-    reserialize_any_from_content("test/data_fld_maplink.elf", |reader, domain| {
+    reserialize_any_from_content("test/data_fld_maplink.elf", FileType::Maplink, |reader, domain| {
+        read_maplink(reader, domain)
+    });
+}
+
+#[test]
+fn elf_ident_padding_matches_original() {
+    let (_, elf_file, data) = read_any_from_content("test/data_fld_maplink.elf", FileType::Maplink, |reader, domain| {
         read_maplink(reader, domain)
     });
+    assert_eq!(elf_file.header.ident_padding_byte(), data.elf_ident_padding_byte());
+    
+    let (_, elf_file, data) = read_any_from_content("test/data_shop.elf", FileType::Shop, |reader, domain| {
+        read_shops(reader, domain)
+    });
+    assert_eq!(elf_file.header.ident_padding_byte(), data.elf_ident_padding_byte());
+}
+
+#[test]
+fn section_name_table_matches_original() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&input_file[..])).unwrap();
+    
+    assert_eq!(elf_file.section_name_table(), elf_file.meta_sections[".shstrtab"].content);
+}
+
+fn assert_content_survives_round_trip(path: &str, file_type: FileType) {
+    let input_file = fs::read(path).unwrap();
+    
+    let data = disassemble_bytes(&input_file, file_type).unwrap();
+    let rebuilt = reassemble_bytes(&data).unwrap();
+    
+    assert_eq!(disassemble_bytes(&rebuilt, file_type).unwrap(), data);
+}
+
+#[test]
+fn maplink_content_survives_round_trip() {
+    assert_content_survives_round_trip("test/data_fld_maplink.elf", FileType::Maplink);
+}
+
+#[test]
+fn mapid_content_survives_round_trip() {
+    assert_content_survives_round_trip("test/data_fld_mapid.elf", FileType::MapId);
+}
+
+#[test]
+fn lct_content_survives_round_trip() {
+    assert_content_survives_round_trip("test/data_lct.elf", FileType::Lct);
+}
+
+/// Small xorshift generator, so that the corrupted inputs are the same on every run.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    
+    fn below(&mut self, max: usize) -> usize {
+        (self.next() % max as u64) as usize
+    }
+}
+
+/// Corrupts a valid file in many different ways and makes sure reading it never panics.
+/// Whether reading succeeds does not matter, since some corruptions still result in a valid file.
+fn assert_malformed_input_does_not_panic(input_file: &[u8], name: &str, file_type: FileType) {
+    let mut rng = XorShift(0x5eed_1234_abcd_9876);
+    
+    for i in 0..500 {
+        let mut bytes = input_file.to_vec();
+        
+        let mutation = if i % 5 == 0 {
+            let len = rng.below(bytes.len());
+            bytes.truncate(len);
+            format!("truncated to 0x{len:x} bytes")
+        } else {
+            let offset = rng.below(bytes.len());
+            let mask = (rng.below(0xff) + 1) as u8;
+            bytes[offset] ^= mask;
+            format!("byte 0x{offset:x} xored with 0x{mask:02x}")
+        };
+        
+        let result = panic::catch_unwind(|| {
+            let _ = ElfContainer::from_reader(&mut Cursor::new(&bytes[..]));
+            let _ = disassemble_bytes(&bytes, file_type);
+        });
+        assert!(result.is_ok(), "Reading {name} panicked after it got {mutation}");
+    }
+}
+
+#[test]
+fn malformed_maplink_does_not_panic() {
+    assert_malformed_input_does_not_panic(&fs::read("test/data_fld_maplink.elf").unwrap(), "data_fld_maplink.elf", FileType::Maplink);
+}
+
+#[test]
+fn malformed_mapid_does_not_panic() {
+    assert_malformed_input_does_not_panic(&fs::read("test/data_fld_mapid.elf").unwrap(), "data_fld_mapid.elf", FileType::MapId);
+}
+
+#[test]
+fn malformed_shop_does_not_panic() {
+    assert_malformed_input_does_not_panic(&fs::read("test/data_shop.elf").unwrap(), "data_shop.elf", FileType::Shop);
+}
+
+#[test]
+fn malformed_lct_does_not_panic() {
+    assert_malformed_input_does_not_panic(&fs::read("test/data_lct.elf").unwrap(), "data_lct.elf", FileType::Lct);
+}
+
+/// Builds a small data_dispos.elf with one area containing one npc, since dispos files cannot be rebuilt yet.
+/// All pointers are relocations against the section symbols of .data and .rodata.
+fn build_dispos_file() -> Vec<u8> {
+    const DATA_SYMBOL: u32 = 1;
+    const RODATA_SYMBOL: u32 = 2;
+    
+    let mut data = vec![0u8; 0x3c + 0x138];
+    // table length (including the trailing null), npc count of the area and npc count of the map
+    for (offset, value) in [(0x0, 2u32), (0x14, 2), (0x38, 1)] {
+        data[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    let rodata = b"area\0map\0npc\0type\0".to_vec();
+    
+    let pointers = [
+        (0x04, DATA_SYMBOL, 0x0c), // area table -> area
+        (0x0c, RODATA_SYMBOL, 0), // area id
+        (0x10, DATA_SYMBOL, 0x28), // npcs of the area
+        (0x28, DATA_SYMBOL, 0x30), // -> DisposNpc
+        (0x30, RODATA_SYMBOL, 5), // map id
+        (0x34, DATA_SYMBOL, 0x3c), // npcs of the map
+        (0x3c, RODATA_SYMBOL, 9), // npc id
+        (0x40, RODATA_SYMBOL, 13), // npc type
+    ];
+    let mut rela = Cursor::new(Vec::new());
+    for (offset, symbol_index, addend) in pointers {
+        Relocation::with_symbol(offset, symbol_index, R_PPC_ADDR32, addend).write(&mut rela).unwrap();
+    }
+    
+    // null symbol and the section symbols of .data and .rodata, followed by the globals
+    let mut symbols = vec![
+        SymbolHeader::default(),
+        SymbolHeader { st_info: 3, st_shndx: 1, ..Default::default() },
+        SymbolHeader { st_info: 3, st_shndx: 3, ..Default::default() },
+    ];
+    let mut strtab = b"\0".to_vec();
+    for (name, st_value, st_size) in [
+        ("all_disposDataTblLen__Q2_4data10DisposData", 0, 4),
+        ("all_disposDataTbl__Q2_4data10DisposData", 4, 8),
+    ] {
+        symbols.push(SymbolHeader { st_name: strtab.len() as u32, st_value, st_size, st_info: 0x11, st_other: 0, st_shndx: 1 });
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+    let mut symtab = Cursor::new(Vec::new());
+    for symbol in &symbols {
+        symbol.write(&mut symtab).unwrap();
+    }
+    
+    let mut elf_file = ElfContainer::new(ElfHeader {
+        e_ident: ELF_HEADER_IDENT,
+        e_type: ET_REL,
+        e_machine: EM_PPC,
+        e_version: 1,
+        e_entry: 0,
+        e_phoff: 0,
+        e_shoff: u32::MAX,
+        e_flags: EF_PPC_EMB,
+        e_ehsize: 0x34,
+        e_phentsize: 0,
+        e_phnum: 0,
+        e_shentsize: 0x28,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    });
+    elf_file.add_content_section_with_relocations(".data", 4, data, rela.into_inner());
+    elf_file.add_content_section_with_relocations(".rodata", 4, rodata, Vec::new());
+    elf_file.add_string_table_raw(".shstrtab", 0, 1, elf_file.section_name_table());
+    elf_file.add_symbol_table_raw(".symtab", 0, 3, 4, symtab.into_inner());
+    elf_file.add_string_table_raw(".strtab", 0, 1, strtab);
+    elf_file.update_section_links();
+    
+    elf_file.to_bytes().unwrap()
+}
+
+#[test]
+fn malformed_dispos_does_not_panic() {
+    let input_file = build_dispos_file();
+    
+    let npc = Npc { id: "npc".to_owned(), r#type: "type".to_owned(), ..Default::default() };
+    let area = DisposArea {
+        id: "area".to_owned(),
+        map_npcs: vec![DisposNpc { map_id: "map".to_owned(), npcs: vec![npc] }],
+        map_mobjs: Vec::new(),
+        map_items: Vec::new(),
+    };
+    assert_eq!(disassemble_bytes(&input_file, FileType::Dispos).unwrap(), FileData::Dispos(vec![area]));
+    
+    assert_malformed_input_does_not_panic(&input_file, "data_dispos.elf", FileType::Dispos);
+}
+
+#[test]
+fn malformed_chr_does_not_panic() {
+    let npc = NpcDef {
+        id: "npc".to_owned(),
+        models: ModelList::Models(vec!["a_model".to_owned(), "b_model".to_owned()]),
+        ..Default::default()
+    };
+    let mobj = MobjDef { id: "mobj".to_owned(), ..Default::default() };
+    let data = FileData::Chr(ChrData { npc_data: vec![NpcDefPtr(npc)], mobj_data: vec![MobjDefPtr(mobj)] });
+    
+    let input_file = reassemble_bytes(&data).unwrap();
+    assert_malformed_input_does_not_panic(&input_file, "data_chr.elf", FileType::Chr);
+}
+
+#[test]
+fn rename_symbol_keeps_other_symbols() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let mut elf_file = ElfContainer::from_reader(&mut Cursor::new(&input_file[..])).unwrap();
+    
+    let old_symbols = elf_file.symbols.clone();
+    elf_file.rename_symbol("datas__Q3_4data3fld7maplink", "renamedDatas__Q3_4data3fld7maplink").unwrap();
+    
+    let bytes = elf_file.to_bytes().unwrap();
+    let renamed = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    assert_eq!(renamed.symbols.len(), old_symbols.len());
+    for (old_symbol, symbol) in old_symbols.iter().zip(&renamed.symbols) {
+        if old_symbol.name == "datas__Q3_4data3fld7maplink" {
+            assert_eq!(symbol.name, "renamedDatas__Q3_4data3fld7maplink");
+        } else {
+            assert_eq!(symbol.name, old_symbol.name);
+        }
+        assert_eq!(symbol.offset(), old_symbol.offset());
+    }
+    
+    assert!(renamed.symbols.get("datas__Q3_4data3fld7maplink").is_none());
+    assert!(renamed.symbols.get("renamedDatas__Q3_4data3fld7maplink").is_some());
+}
+
+#[test]
+fn rename_symbol_with_tail_shared_name() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let mut elf_file = reassemble_elf_container(&data, None).unwrap();
+    
+    // let the two named symbols share "foobar", with the second one only using its tail
+    let strtab = elf_file.meta_sections.get_mut(".strtab").unwrap();
+    let foobar = strtab.content.len() as u32;
+    strtab.content.extend_from_slice(b"foobar\0");
+    
+    let symtab = elf_file.meta_sections.get_mut(".symtab").unwrap();
+    let mut headers: Vec<SymbolHeader> = symtab.read_entries().unwrap();
+    let named: Vec<usize> = (0..headers.len()).filter(|&index| headers[index].st_info == 0x11).collect();
+    headers[named[0]].st_name = foobar;
+    headers[named[1]].st_name = foobar + 3;
+    let mut writer = Cursor::new(Vec::new());
+    for header in &headers {
+        header.write(&mut writer).unwrap();
+    }
+    symtab.content = writer.into_inner();
+    
+    let bytes = elf_file.to_bytes().unwrap();
+    let mut elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    assert!(elf_file.symbols.contains("foobar") && elf_file.symbols.contains("bar"));
+    
+    // "bar" is not shared anymore after the first rename, so it gets renamed in place
+    elf_file.rename_symbol("foobar", "renamed").unwrap();
+    elf_file.rename_symbol("bar", "alsoRenamed").unwrap();
+    
+    let bytes = elf_file.to_bytes().unwrap();
+    let renamed = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    assert_eq!(renamed.symbols.len(), elf_file.symbols.len());
+    assert!(renamed.symbols.contains("renamed") && renamed.symbols.contains("alsoRenamed"));
+    assert!(!renamed.symbols.contains("foobar") && !renamed.symbols.contains("bar"));
+}
+
+#[test]
+fn disassemble_returns_container() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let (data, elf_file) = disassemble(&input_file, FileType::Maplink).unwrap();
+    
+    assert_eq!(data.file_type(), FileType::Maplink);
+    assert!(elf_file.symbols.contains(FileType::Maplink.anchor_symbol_name()));
+}
+
+#[test]
+fn symbols_keep_symtab_indices() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&input_file[..])).unwrap();
+    
+    let symtab = &elf_file.meta_sections[".symtab"];
+    assert_eq!(elf_file.symbols.len(), symtab.content.len() / size_of::<SymbolHeader>());
+    
+    assert_eq!(elf_file.symbols.index_of(""), Some(0));
+}
+
+#[test]
+fn table_entries_use_entry_size() {
+    let relocation = Relocation::with_symbol(0x10, 2, R_PPC_ADDR32, 4);
+    
+    // two relocations padded to 0x10 bytes each
+    let mut content = Vec::new();
+    for _ in 0..2 {
+        let mut entry = Cursor::new(Vec::new());
+        relocation.write(&mut entry).unwrap();
+        content.extend(entry.into_inner());
+        content.extend([0xff; 4]);
+    }
+    
+    let mut section = Section {
+        content,
+        ..Default::default()
+    };
+    section.header.sh_entsize = 0x10;
+    assert_eq!(section.entry_count::<Relocation>(), 2);
+    
+    let relocations: Vec<Relocation> = section.read_entries().unwrap();
+    assert_eq!(relocations.len(), 2);
+    assert_eq!((relocations[1].offset, relocations[1].info, relocations[1].addend), (0x10, 0x201, 4));
+}
+
+#[test]
+fn verify_original_and_rebuilt_files() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let (data, elf_file) = disassemble(&input_file, FileType::Maplink).unwrap();
+    elf_file.verify().unwrap();
+    
+    let mut rebuilt = reassemble_elf_container(&data, None).unwrap();
+    rebuilt.verify().unwrap();
+    
+    rebuilt.header.e_shnum += 1;
+    assert!(rebuilt.verify().is_err());
+    
+    for name in [".symtab", ".strtab", ".shstrtab"] {
+        let mut rebuilt = reassemble_elf_container(&data, None).unwrap();
+        rebuilt.meta_sections.shift_remove(name);
+        assert!(rebuilt.verify().is_err());
+    }
+}
+
+#[test]
+fn section_with_relocations_applied() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let (_, elf_file) = disassemble(&input_file, FileType::Maplink).unwrap();
+    
+    let section_name = FileType::Maplink.content_section_name();
+    let section = elf_file.get_section(section_name).unwrap();
+    let linked = elf_file.section_with_relocations_applied(section_name).unwrap();
+    assert_eq!(linked, link_section_debug(section, &elf_file.symbols).unwrap());
+    assert_eq!(linked.len(), section.content.len());
+    
+    assert!(elf_file.section_with_relocations_applied(".nonexistent").is_err());
 }
 
 #[test]
@@ -79,9 +462,24 @@ fn reserialize_shop_directly() {
 
 #[test]
 fn reserialize_shop_from_content() {
-    reserialize_any_from_content("test/data_shop.elf", |reader, domain| {
+    reserialize_any_from_content("test/data_shop.elf", FileType::Shop, |reader, domain| {
+        read_shops(reader, domain)
+    });
+}
+
+#[test]
+fn shop_symbol_names_match_original() {
+    let (_, elf_file, data) = read_any_from_content("test/data_shop.elf", FileType::Shop, |reader, domain| {
         read_shops(reader, domain)
     });
+    
+    let out_elf = reassemble_elf_container(&data, None).unwrap();
+    let out_bytes = out_elf.to_bytes().unwrap();
+    let out_elf = ElfContainer::from_reader(&mut Cursor::new(out_bytes.as_slice())).unwrap();
+    
+    let original_names: Vec<&String> = elf_file.symbols.iter().map(|symbol| &symbol.name).collect();
+    let names: Vec<&String> = out_elf.symbols.iter().map(|symbol| &symbol.name).collect();
+    assert_eq!(original_names, names);
 }
 
 #[test]
@@ -91,7 +489,1215 @@ fn reserialize_mapid_directly() {
 
 #[test]
 fn reserialize_mapid_from_content() {
-    reserialize_any_from_content("test/data_fld_mapid.elf", |reader, domain| {
+    reserialize_any_from_content("test/data_fld_mapid.elf", FileType::MapId, |reader, domain| {
         read_mapid(reader, domain)
     });
 }
+
+#[test]
+fn reserialize_lct_directly() {
+    reserialize_any_directly("test/data_lct.elf");
+}
+
+#[test]
+fn reserialize_lct_from_content() {
+    reserialize_any_from_content("test/data_lct.elf", FileType::Lct, |reader, domain| {
+        read_lct(reader, domain)
+    });
+}
+
+#[test]
+fn lct_kind_round_trip() {
+    for kind in [LctKind::None, LctKind::Unknown(0x1234)] {
+        let raw = u32::from(kind);
+        assert_eq!(LctKind::from(raw), kind);
+        
+        let yaml = serde_yaml_bw::to_string(&kind).unwrap();
+        assert_eq!(yaml.trim(), raw.to_string());
+        let deserialized: LctKind = serde_yaml_bw::from_str(&yaml).unwrap();
+        assert_eq!(deserialized, kind);
+    }
+}
+
+#[test]
+fn exact_f32_round_trip() {
+    #[derive(Serialize, Deserialize)]
+    struct Value {
+        #[serde(with = "crate::util::exact_f32")]
+        value: f32,
+    }
+    
+    for bits in [0x7fc00001, 0xffc00000, 0x80000000, 0x3fc00000, 0x3dcccccd, 0x7f800000, 0x00000001] {
+        let yaml = serde_yaml_bw::to_string(&Value { value: f32::from_bits(bits) }).unwrap();
+        let deserialized: Value = serde_yaml_bw::from_str(&yaml).unwrap();
+        assert_eq!(deserialized.value.to_bits(), bits, "{yaml}");
+    }
+    
+    let deserialized: Value = serde_yaml_bw::from_str("value: 2").unwrap();
+    assert_eq!(deserialized.value, 2.0);
+}
+
+#[test]
+fn numeric_fields_default_to_zero() {
+    let link: Link = serde_yaml_bw::from_str(indoc! {"
+        id: link_01
+        destination: mac_01
+        link_type: ''
+        zone_id: ''
+        player_direction: 90.0
+        player_facing: ''
+        door_type: ''
+        field_0x1c: ''
+        pipe_cam_script_enter: ''
+        pipe_cam_script_exit: ''
+        field_0x2c: ''
+        enter_function: ''
+        exit_function: ''
+        field_0x38: ''
+    "}).unwrap();
+    
+    assert_eq!(link.field_0x28, 0);
+    assert_eq!(link.player_direction, 90.0);
+}
+
+#[test]
+fn wrong_file_type_suggests_detected_type() {
+    let mut symbols = SymbolTable::new();
+    let name = FileType::Maplink.anchor_symbol_name().to_string();
+    symbols.push(Symbol::new(SymbolHeader::default(), name));
+    
+    assert_eq!(FileType::Maplink.ensure_matches(&symbols), Ok(()));
+    assert_eq!(FileType::Shop.ensure_matches(&symbols), Err(FileTypeMismatch {
+        expected: FileType::Shop,
+        detected: Some(FileType::Maplink),
+    }));
+    assert_eq!(FileType::Shop.ensure_matches(&SymbolTable::new()), Err(FileTypeMismatch {
+        expected: FileType::Shop,
+        detected: None,
+    }));
+}
+
+#[test]
+fn missing_symbol_suggests_similar_names() {
+    let mut symbols = SymbolTable::new();
+    for name in ["", "shopList__Q2_4data4shop", "shopListLen__Q2_4data4shop", "datas__Q3_4data3fld5mapid"] {
+        symbols.push(Symbol::new(SymbolHeader::default(), name.to_string()));
+    }
+    
+    let relocations = Default::default();
+    let state = ReadState::default();
+    let domain = ElfReadDomain::new(&[], &relocations, &symbols, &state);
+    
+    let err = domain.find_symbol("shopList__Q2_4data5shop").unwrap_err();
+    let Ok(PaintelfError::SymbolNotFound { suggestions, .. }) = err.downcast() else {
+        panic!("Expected SymbolNotFound error");
+    };
+    assert_eq!(suggestions, ["shopList__Q2_4data4shop", "shopListLen__Q2_4data4shop", "datas__Q3_4data3fld5mapid"]);
+    
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("same", "same"), 0);
+}
+
+#[test]
+fn strings_are_read_from_rodata_section() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader::default(), String::new()));
+    
+    let relocations = [(Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 4))].into_iter().collect();
+    let content = b"\0\0\0\0data\0";
+    let rodata = b"\0\0\0\0rodata\0";
+    
+    let state = ReadState::default();
+    let domain = ElfReadDomain::new(content, &relocations, &symbols, &state);
+    assert_eq!(domain.read_string(&mut Cursor::new(content)).unwrap(), "data");
+    
+    let domain = domain.with_rodata_section(rodata);
+    assert_eq!(domain.read_string(&mut Cursor::new(content)).unwrap(), "rodata");
+}
+
+#[test]
+fn symbol_pointers_name_global_symbols() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x10, st_info: 0x11, ..Default::default() }, "npc_model".to_owned()));
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x20, st_info: 0x1, ..Default::default() }, "lbbb".to_owned()));
+    
+    let relocations = [
+        (Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 0)),
+        (Pointer(4), Relocation::with_symbol(4, 0, R_PPC_ADDR32, 4)),
+        (Pointer(8), Relocation::with_symbol(8, 1, R_PPC_ADDR32, 0)),
+    ].into_iter().collect();
+    let content = [0; 12];
+    
+    let state = ReadState::default();
+    let domain = ElfReadDomain::new(&content, &relocations, &symbols, &state);
+    let mut reader = Cursor::new(&content[..]);
+    let pointers: Vec<SymbolPointer> = (0..3).map(|_| domain.read_symbol_pointer(&mut reader).unwrap()).collect();
+    
+    assert_eq!(pointers, [
+        SymbolPointer::Symbol("npc_model".to_owned()),
+        SymbolPointer::Offset(Pointer(0x14)),
+        SymbolPointer::Offset(Pointer(0x20)),
+    ]);
+    
+    let yaml = serde_yaml_bw::to_string(&pointers).unwrap();
+    assert!(yaml.contains("@npc_model"), "{yaml}");
+    assert_eq!(serde_yaml_bw::from_str::<Vec<SymbolPointer>>(&yaml).unwrap(), pointers);
+}
+
+#[test]
+fn shop_without_items() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader::default(), String::new()));
+    
+    // shop id pointer followed by a null items pointer
+    let relocations = [(Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 8))].into_iter().collect();
+    let content = b"\0\0\0\0\0\0\0\0empty\0";
+    
+    let state = ReadState::default();
+    let domain = ElfReadDomain::new(content, &relocations, &symbols, &state);
+    let shop = Shop::from_reader(&mut Cursor::new(&content[..]), domain).unwrap();
+    assert_eq!(shop, Shop { shop_id: "empty".to_owned(), items: Vec::new() });
+    
+    let data = FileData::Shop(vec![
+        shop,
+        Shop {
+            shop_id: "full".to_owned(),
+            items: vec![SoldItem { item_id: Some("item".to_owned()), requirement: None }],
+        },
+    ]);
+    let rebuilt = reassemble_bytes(&data).unwrap();
+    assert_eq!(disassemble_bytes(&rebuilt, FileType::Shop).unwrap(), data);
+}
+
+#[test]
+fn elf_container_debug_is_summarized() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let bytes = reassemble_bytes(&data).unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    let debug = format!("{elf_file:?}");
+    let rodata_size = elf_file.require_section(".rodata").unwrap().content.len();
+    
+    assert!(debug.starts_with(&format!("ElfContainer {{ symbols: <{}>, content_sections: [.rodata <{rodata_size} bytes>]",
+        elf_file.symbols.len())), "{debug}");
+    assert!(!debug.contains("shop"), "{debug}");
+}
+
+#[test]
+fn strings_are_listed_with_offsets() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let bytes = reassemble_bytes(&data).unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    let rodata = &elf_file.require_section(".rodata").unwrap().content;
+    let strings = elf_file.strings(".rodata").unwrap();
+    let (offset, _) = strings.iter().find(|(_, string)| *string == "shop").unwrap();
+    assert_eq!(read_string(rodata, *offset).unwrap(), "shop");
+    
+    let symbol_names = elf_file.strings(".strtab").unwrap();
+    assert!(symbol_names.iter().any(|(_, name)| *name == "shopList__Q2_4data4shop"));
+    assert!(symbol_names.iter().all(|(_, name)| !name.is_empty()));
+}
+
+#[test]
+fn strings_can_be_replaced() {
+    let shop = |shop_id: &str, item_id: &str| Shop {
+        shop_id: shop_id.to_owned(),
+        items: vec![SoldItem { item_id: Some(item_id.to_owned()), requirement: None }],
+    };
+    let data = FileData::Shop(vec![shop("first", "apple"), shop("second", "pear")]);
+    let bytes = reassemble_bytes(&data).unwrap();
+    let mut elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    let mut replacements = HashMap::new();
+    replacements.insert("first".to_owned(), "a much longer shop name".to_owned());
+    replacements.insert("pear".to_owned(), "fig".to_owned());
+    replacements.insert("unused".to_owned(), "something".to_owned());
+    elf_file.replace_strings(&replacements).unwrap();
+    
+    let expected = FileData::Shop(vec![shop("a much longer shop name", "apple"), shop("second", "fig")]);
+    assert_eq!(disassemble_bytes(&elf_file.to_bytes().unwrap(), FileType::Shop).unwrap(), expected);
+}
+
+#[test]
+fn missing_content_section_lists_candidates() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let elf_file = reassemble_elf_container(&data, None).unwrap();
+    
+    for file_type in FileType::ALL {
+        assert_eq!(file_type.content_section_names()[0], file_type.content_section_name());
+    }
+    
+    let state = ReadState::default();
+    assert!(content_section_and_domain(&elf_file, FileType::Shop, &state).is_ok());
+    
+    let err = content_section_and_domain(&elf_file, FileType::Lct, &state).err().unwrap();
+    assert!(err.to_string().starts_with("Could not find any of the sections .data, .sdata for lct data; available: "), "{err}");
+}
+
+#[test]
+fn model_lists_are_followed() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader::default(), String::new()));
+    
+    // model list pointer, then the list itself followed by the strings
+    let relocations = [
+        (Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 4)),
+        (Pointer(4), Relocation::with_symbol(4, 0, R_PPC_ADDR32, 0x10)),
+        (Pointer(8), Relocation::with_symbol(8, 0, R_PPC_ADDR32, 0x12)),
+    ].into_iter().collect();
+    let content = b"\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0a\0bc\0";
+    let state = ReadState::default();
+    let domain = ElfReadDomain::new(content, &relocations, &symbols, &state);
+    
+    let models = domain.read_null_terminated_string_array(&mut Cursor::new(&content[..]), Pointer(4)).unwrap();
+    assert_eq!(models, ["a", "bc"]);
+    
+    let model_list: ModelList = domain.read(&mut Cursor::new(&content[..])).unwrap();
+    assert_eq!(model_list, ModelList::Models(vec!["a".to_owned(), "bc".to_owned()]));
+    
+    // the null terminator of the list is a null pointer
+    let mut reader = Cursor::new(&content[..]);
+    reader.set_position(0xc);
+    assert_eq!(CanRead::<ModelList>::read(domain, &mut reader).unwrap(), ModelList::None);
+    
+    // the first model name is not a list of pointers
+    let mut reader = Cursor::new(&content[..]);
+    reader.set_position(4);
+    assert!(CanRead::<ModelList>::read(domain, &mut reader).is_err());
+    
+    for model_list in [model_list, ModelList::None] {
+        let yaml = serde_yaml_bw::to_string(&model_list).unwrap();
+        assert_eq!(serde_yaml_bw::from_str::<ModelList>(&yaml).unwrap(), model_list);
+    }
+}
+
+/// Follows a chain of nested boxes, returning how many were followed.
+fn read_box_chain(domain: ElfReadDomain, reader: &mut Cursor<&[u8]>) -> Result<u32> {
+    let inner = domain.read_box_nullable(reader, |reader| read_box_chain(domain, reader))?;
+    Ok(inner.map_or(0, |depth| depth + 1))
+}
+
+#[test]
+fn pointer_cycles_are_rejected() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader::default(), String::new()));
+    let content = [0u8; 8];
+    
+    // 0 -> 4 -> 0
+    let relocations = [
+        (Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 4)),
+        (Pointer(4), Relocation::with_symbol(4, 0, R_PPC_ADDR32, 0)),
+    ].into_iter().collect();
+    let state = ReadState::default();
+    let domain = ElfReadDomain::new(&content, &relocations, &symbols, &state);
+    let err = read_box_chain(domain, &mut Cursor::new(&content[..])).unwrap_err();
+    assert!(err.to_string().contains("Pointer cycle"), "{err}");
+    
+    // 0 -> 4 -> null, which also makes sure the boxes of the failed read were released
+    let relocations = [(Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 4))].into_iter().collect();
+    let domain = ElfReadDomain::new(&content, &relocations, &symbols, &state);
+    assert_eq!(read_box_chain(domain, &mut Cursor::new(&content[..])).unwrap(), 1);
+}
+
+#[test]
+fn single_table_can_be_read() {
+    let shops = vec![
+        Shop { shop_id: "a".to_owned(), items: vec![SoldItem { item_id: Some("item".to_owned()), requirement: None }] },
+        Shop { shop_id: "b".to_owned(), items: Vec::new() },
+    ];
+    let bytes = reassemble_bytes(&FileData::Shop(shops.clone())).unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    let table = read_single_table(&elf_file, "shopListLen__Q2_4data4shop", "shopList__Q2_4data4shop", false,
+        |reader, domain| Shop::from_reader(reader, domain)).unwrap();
+    assert_eq!(table, shops);
+    
+    // symbols keep referring to the sections by their index in the original file
+    let mut elf_file = elf_file;
+    elf_file.content_sections.shift_insert(1, ".extra".to_owned(), Section { name: ".extra".to_owned(), ..Default::default() });
+    let table = read_single_table(&elf_file, "shopListLen__Q2_4data4shop", "shopList__Q2_4data4shop", false,
+        |reader, domain| Shop::from_reader(reader, domain)).unwrap();
+    assert_eq!(table, shops);
+}
+
+#[test]
+fn container_can_be_read_as_file_data() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let bytes = reassemble_bytes(&data).unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    assert_eq!(elf_file.read_file_data(FileType::Shop).unwrap(), data);
+    assert!(elf_file.read_file_data(FileType::Maplink).is_err());
+}
+
+#[test]
+fn container_can_be_written_to_any_writer() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let elf_file = reassemble_elf_container(&data, None).unwrap();
+    let bytes = elf_file.to_bytes().unwrap();
+    
+    // e.g. a region of a bigger buffer that is patched in place
+    let mut buffer = vec![0xff; bytes.len() + 0x10];
+    let mut writer = Cursor::new(&mut buffer[..]);
+    elf_file.write_to(&mut writer).unwrap();
+    assert_eq!(writer.position() as usize, bytes.len());
+    assert_eq!(buffer[..bytes.len()], bytes);
+    assert_eq!(buffer[bytes.len()..], [0xff; 0x10]);
+    
+    let mut writer = Cursor::new(vec![0; 4]);
+    writer.set_position(4);
+    assert!(elf_file.write_to(&mut writer).is_err());
+}
+
+#[test]
+fn section_sizes_follow_section_header_order() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let elf_file = reassemble_elf_container(&data, None).unwrap();
+    
+    let sizes = elf_file.section_sizes();
+    let names: Vec<&str> = sizes.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, elf_file.section_header_order()[1..]);
+    
+    let total: usize = sizes.iter().map(|(_, size)| size).sum();
+    assert!(total < elf_file.to_bytes().unwrap().len());
+}
+
+#[test]
+fn content_sections_use_content_alignment() {
+    let data = FileData::Lct(Vec::new());
+    let elf_file = reassemble_elf_container(&data, None).unwrap();
+    
+    for section in elf_file.content_sections.values().filter(|section| !section.name.is_empty()) {
+        assert_eq!(section.header.sh_addralign, 4, "{}", section.name);
+    }
+}
+
+#[test]
+fn file_data_summary() {
+    let data = FileData::Shop(vec![
+        Shop { shop_id: "a".to_owned(), items: vec![SoldItem::default(); 2] },
+        Shop { shop_id: "b".to_owned(), items: vec![SoldItem::default(); 3] },
+    ]);
+    assert_eq!(data.to_string(), "Shop: 2 shops, 5 items");
+    
+    assert_eq!(FileData::Lct(Vec::new()).to_string(), "Lct: 0 areas, 0 maps, 0 lcts");
+}
+
+#[test]
+fn default_map_definitions_can_be_rebuilt() {
+    let map = MapDefinition { group_id: "W1".to_owned(), map_id: "W1C1".to_owned(), ..Default::default() };
+    assert_eq!(map.field_0x20, 0);
+    assert!(map.description.is_empty());
+    
+    let data = FileData::MapId(vec![MapGroup { id: "W1".to_owned(), maps: vec![map] }]);
+    let rebuilt = reassemble_bytes(&data).unwrap();
+    assert_eq!(disassemble_bytes(&rebuilt, FileType::MapId).unwrap(), data);
+}
+
+#[test]
+fn chr_can_be_rebuilt() {
+    let npc = NpcDef {
+        id: "npc".to_owned(),
+        models: ModelList::Models(vec!["a_model".to_owned(), "b_model".to_owned()]),
+        ..Default::default()
+    };
+    let other_npc = NpcDef { id: "other_npc".to_owned(), ..Default::default() };
+    let mobj = MobjDef { id: "mobj".to_owned(), ..Default::default() };
+    
+    let data = FileData::Chr(ChrData {
+        npc_data: vec![NpcDefPtr(npc), NpcDefPtr(other_npc)],
+        mobj_data: vec![MobjDefPtr(mobj)],
+    });
+    let rebuilt = reassemble_bytes(&data).unwrap();
+    assert_eq!(disassemble_bytes(&rebuilt, FileType::Chr).unwrap(), data);
+}
+
+#[test]
+fn strict_yaml_rejects_unknown_fields() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let yaml = serde_yaml_bw::to_string(&data).unwrap();
+    
+    // add a misspelled copy of the items field
+    let items_line = yaml.lines().find(|line| line.contains("items:")).unwrap();
+    let typo_line = items_line.replace("items:", "itmes:");
+    let typo_yaml = yaml.replacen(items_line, &format!("{items_line}\n{typo_line}"), 1);
+    
+    assert_eq!(FileData::from_yaml(&yaml, true).unwrap(), data);
+    assert_eq!(FileData::from_yaml(&typo_yaml, false).unwrap(), data);
+    
+    let err = FileData::from_yaml(&typo_yaml, true).unwrap_err();
+    assert!(err.to_string().contains("itmes"), "{err}");
+}
+
+#[test]
+fn duplicate_ids_are_rejected() {
+    let area = |id: &str| DisposArea { id: id.to_owned(), map_npcs: Vec::new(), map_mobjs: Vec::new(), map_items: Vec::new() };
+    
+    assert!(FileData::Dispos(vec![area("a"), area("b")]).ensure_unique_ids().is_ok());
+    
+    let data = FileData::Dispos(vec![area("a"), area("b"), area("a")]);
+    let err = reassemble_elf_container(&data, None).unwrap_err();
+    assert_eq!(err.to_string(), "Duplicate id a in dispos");
+}
+
+#[test]
+fn strings_can_be_placed_first() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let options = ReassembleOptions {
+        leading_strings: true,
+        ..Default::default()
+    };
+    
+    let elf_file = reassemble_elf_container_with_options(&data, options, None).unwrap();
+    assert!(elf_file.require_section(".rodata").unwrap().content.starts_with(b"shop\0"));
+    assert_eq!(disassemble_bytes(&elf_file.to_bytes().unwrap(), FileType::Shop).unwrap(), data);
+}
+
+#[test]
+fn section_count_is_derived_when_writing() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    
+    let mut elf_file = reassemble_elf_container(&data, None).unwrap();
+    let expected = (elf_file.header.e_shnum, elf_file.header.e_shstrndx);
+    elf_file.header.e_shnum = 2;
+    elf_file.header.e_shstrndx = 0;
+    
+    let bytes = elf_file.to_bytes().unwrap();
+    let reread = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    reread.verify().unwrap();
+    assert_eq!((reread.header.e_shnum, reread.header.e_shstrndx), expected);
+    assert_eq!(disassemble_bytes(&bytes, FileType::Shop).unwrap(), data);
+}
+
+#[test]
+fn template_sections_are_kept() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    
+    let mut template = reassemble_elf_container(&data, None).unwrap();
+    template.add_content_section(".extra", 4, vec![1, 2, 3, 4]);
+    
+    let options = ReassembleOptions { template: Some(&template), ..Default::default() };
+    let elf_file = reassemble_elf_container_with_options(&data, options, None).unwrap();
+    let bytes = elf_file.to_bytes().unwrap();
+    let reread = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    assert_eq!(reread.require_section(".extra").unwrap().content, [1, 2, 3, 4]);
+    assert_eq!(disassemble_bytes(&bytes, FileType::Shop).unwrap(), data);
+    
+    template.header.e_flags = 0x1234;
+    let options = ReassembleOptions { template: Some(&template), ..Default::default() };
+    let elf_file = reassemble_elf_container_with_options(&data, options, None).unwrap();
+    assert_eq!(elf_file.header.e_flags, 0x1234);
+    assert_eq!(reassemble_elf_container(&data, None).unwrap().header.e_flags, EF_PPC_EMB);
+    
+    let extra = template.content_sections.get_mut(".extra").unwrap();
+    extra.relocations = Some([(Pointer(0), Relocation::with_symbol(0, 1, R_PPC_ADDR32, 0))].into_iter().collect());
+    let options = ReassembleOptions { template: Some(&template), ..Default::default() };
+    assert!(reassemble_elf_container_with_options(&data, options, None).is_err());
+}
+
+#[test]
+fn symbols_containing_offset() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x10, st_size: 0x10, ..Default::default() }, "outer".to_owned()));
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x14, st_size: 0x4, ..Default::default() }, "inner".to_owned()));
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x18, st_size: 0, ..Default::default() }, "empty".to_owned()));
+    
+    let names = |offset| symbols.symbols_at_offset(offset).iter().map(|symbol| symbol.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names(0x14), ["outer", "inner"]);
+    assert_eq!(names(0x18), ["outer"]);
+    assert!(names(0x20).is_empty());
+}
+
+#[test]
+fn internal_symbol_names_follow_offsets() {
+    let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
+    let mut domain = ElfWriteDomain::new(0, false);
+    
+    let names = [
+        SymbolName::Internal('.'),
+        SymbolName::Internal('l'),
+        SymbolName::Internal('.'),
+        SymbolName::InternalNamed("abc".to_owned()),
+        SymbolName::InternalNamed("abc0".to_owned()),
+    ];
+    let mut declarations = Vec::new();
+    for name in names {
+        let offset = ctx.heap_token_at_current_pos().unwrap();
+        declarations.push(SymbolDeclaration { name, offset, size: 4, category: None });
+        0u32.to_writer(&mut ctx, &mut domain).unwrap();
+    }
+    
+    // names depend on the offsets, not on the order of declaration
+    declarations.reverse();
+    
+    let mut resolver = HeapResolver::default();
+    let heap_id = ctx.heap_id_of(DataCategory::Rodata);
+    resolver.write_heap(&mut domain, heap_id, ctx.heap(&DataCategory::Rodata).unwrap()).unwrap();
+    
+    assign_internal_symbol_names(&mut declarations, &resolver.block_offsets).unwrap();
+    
+    let names: Vec<&str> = declarations.iter()
+        .map(|symbol| symbol.name.as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a", "ab", ".b", "l", "."]);
+}
+
+#[test]
+fn internal_symbol_name_order_special_cases() {
+    assert_eq!(internal_symbol_name_order("abc", "abd"), Ordering::Less);
+    assert_eq!(internal_symbol_name_order("abc", "abc"), Ordering::Equal);
+    
+    // extensions starting with a char below 'P' come first
+    assert_eq!(internal_symbol_name_order("abc0", "abc"), Ordering::Less);
+    assert_eq!(internal_symbol_name_order("abcO", "abc"), Ordering::Less);
+    assert_eq!(internal_symbol_name_order("abc", "abcA"), Ordering::Greater);
+    
+    // others don't
+    assert_eq!(internal_symbol_name_order("abcP", "abc"), Ordering::Greater);
+    assert_eq!(internal_symbol_name_order("abc_", "abc"), Ordering::Greater);
+    assert_eq!(internal_symbol_name_order("abc", "abcx"), Ordering::Less);
+}
+
+#[test]
+fn symbol_order_can_be_preserved() {
+    let data = FileData::Shop(vec![
+        Shop { shop_id: "b".to_owned(), items: vec![SoldItem::default()] },
+        Shop { shop_id: "a".to_owned(), items: vec![SoldItem::default()] },
+    ]);
+    
+    let options = ReassembleOptions { preserve_symbol_order: true, ..Default::default() };
+    let bytes = reassemble_elf_container_with_options(&data, options, None).unwrap().to_bytes().unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    let names_with_info = |st_info: u8| -> Vec<&str> {
+        elf_file.symbols.iter()
+            .filter(|symbol| symbol.header.st_info == st_info)
+            .map(|symbol| symbol.name.as_str())
+            .collect()
+    };
+    // globals keep their order, internal symbols are not named automatically
+    assert_eq!(names_with_info(0x11), ["shopList__Q2_4data4shop", "shopListLen__Q2_4data4shop"]);
+    assert!(names_with_info(0x1).iter().all(|name| name.is_empty()));
+    assert!(names_with_info(0x10).is_empty());
+    
+    assert_eq!(disassemble_bytes(&bytes, FileType::Shop).unwrap(), data);
+}
+
+#[test]
+fn mismatch_names_containing_symbol() {
+    let data = FileData::Shop(vec![Shop { shop_id: "shop".to_owned(), items: Vec::new() }]);
+    let bytes = reassemble_bytes(&data).unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&bytes[..])).unwrap();
+    
+    let original = &elf_file.require_section(".rodata").unwrap().content;
+    let offset = elf_file.symbols.get("shopList__Q2_4data4shop").unwrap().offset() as usize + 4;
+    let mut modified = original.clone();
+    modified[offset] ^= 1;
+    
+    assert_eq!(describe_mismatch(&elf_file, ".rodata", original, &modified),
+        format!("mismatch at 0x{offset:x}, within symbol shopList__Q2_4data4shop (field offset +0x4)"));
+}
+
+#[test]
+fn symbol_name_generator_skip_first_a() {
+    fn name_after_overflow(skip_first_a: bool) -> String {
+        let mut symbol_name_gen = SymbolNameGenerator::new().with_skip_first_a(skip_first_a);
+        
+        for _ in 0..AUTO_SYMBOL_NAME_CHAR_COUNT {
+            symbol_name_gen.next();
+        }
+        symbol_name_gen.next().to_string()
+    }
+    
+    assert_eq!(name_after_overflow(true), "ba");
+    assert_eq!(name_after_overflow(false), "aa");
+}
+
+#[test]
+fn symbol_name_generator_sequence() {
+    let mut symbol_name_gen = SymbolNameGenerator::new();
+    let names: Vec<String> = (0..200)
+        .map(|_| symbol_name_gen.next().to_string())
+        .collect();
+    
+    assert_eq!(names[0], "");
+    assert_eq!(names[1], "b");
+    assert_eq!(names[92], "`");
+    assert_eq!(names[93], "ba");
+    assert_eq!(names[94], "bb");
+    assert_eq!(names[185], "b`");
+    assert_eq!(names[186], "ca");
+    
+    // the string symbols of the original files were named by the game's linker, in order of their offsets
+    let mut checked = 0;
+    for path in ["test/data_fld_maplink.elf", "test/data_fld_mapid.elf"] {
+        let input_file = fs::read(path).unwrap();
+        let elf_file = ElfContainer::from_reader(&mut Cursor::new(&input_file[..])).unwrap();
+        
+        let mut string_symbols: Vec<&Symbol> = elf_file.symbols.iter()
+            .filter(|symbol| symbol.header.st_info & 0xf != 3 && symbol.name.starts_with(STRING_SYMBOL_PREFIX))
+            .collect();
+        string_symbols.sort_by_key(|symbol| symbol.offset());
+        
+        for (i, symbol) in string_symbols.iter().enumerate().take(names.len()) {
+            assert_eq!(&symbol.name[1..], names[i], "symbol {i} in {path}");
+        }
+        checked = checked.max(string_symbols.len().min(names.len()));
+    }
+    
+    // make sure the files cover names with two characters
+    assert!(checked > AUTO_SYMBOL_NAME_CHAR_COUNT, "only {checked} names could be compared");
+}
+
+#[test]
+fn symbol_name_generator_grows_after_two_characters() {
+    let mut symbol_name_gen = SymbolNameGenerator::new();
+    
+    // "", 92 single character names and 92 * 93 two character names
+    let two_char_count = (AUTO_SYMBOL_NAME_CHAR_COUNT - 1) * AUTO_SYMBOL_NAME_CHAR_COUNT;
+    for _ in 0..AUTO_SYMBOL_NAME_CHAR_COUNT + two_char_count {
+        symbol_name_gen.next();
+    }
+    
+    assert_eq!(symbol_name_gen.next(), "baa");
+}
+
+#[test]
+fn mangle_known_symbol_names() {
+    let known_names: &[(&[&str], &str, &str)] = &[
+        (&["data", "fld", "maplink"], "datas", "datas__Q3_4data3fld7maplink"),
+        (&["data", "fld", "mapid"], "dataCount", "dataCount__Q3_4data3fld5mapid"),
+        (&["data", "shop"], "shopList", "shopList__Q2_4data4shop"),
+        (&["data", "DisposData"], "all_disposDataTbl", "all_disposDataTbl__Q2_4data10DisposData"),
+        (&["data", "chr"], "npcDataTableLen", "npcDataTableLen__Q2_4data3chr"),
+        (&["data", "lct"], "all_lctAnimeDataTbl", "all_lctAnimeDataTbl__Q2_4data3lct"),
+    ];
+    
+    for &(namespaces, name, mangled) in known_names {
+        assert_eq!(mangle_name(namespaces, name), mangled);
+        assert_eq!(demangle_name(mangled), Some((namespaces.to_vec(), name)));
+    }
+    
+    assert_eq!(mangle_name(&["data"], "foo"), "foo__4data");
+    assert_eq!(demangle_name("foo__4data"), Some((vec!["data"], "foo")));
+    assert_eq!(demangle_name("data_fld_maplink.cpp"), None);
+    assert_eq!(demangle_name("foo__Q2_4data"), None);
+}
+
+#[test]
+fn data_count_disagrees_with_symbol_size() {
+    let name = "datas__Q3_4data3fld7maplink".to_string();
+    let symbol = Symbol::new(SymbolHeader {
+        st_size: 99 * MAPLINK_AREA_SIZE,
+        ..Default::default()
+    }, name);
+    
+    assert!(check_count_matches_symbol(99, &symbol, MAPLINK_AREA_SIZE));
+    assert!(!check_count_matches_symbol(100, &symbol, MAPLINK_AREA_SIZE));
+}
+
+#[test]
+fn write_atomic_replaces_file_and_cleans_up() {
+    let dir = env::temp_dir().join("paintelf_write_atomic_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    
+    // successful write
+    let path = dir.join("out.yaml");
+    write_atomic(&path, "first").unwrap();
+    write_atomic(&path, "second").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    assert!(!dir.join("out.yaml.tmp").exists());
+    
+    // renaming onto a directory fails, the temp file should not be left behind
+    let dir_path = dir.join("out_dir");
+    fs::create_dir(&dir_path).unwrap();
+    assert!(write_atomic(&dir_path, "content").is_err());
+    assert!(!dir.join("out_dir.tmp").exists());
+    
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn read_string_out_of_bounds() {
+    let buffer = b"\0abc\0";
+    
+    assert_eq!(read_string(buffer, 1).unwrap(), "abc");
+    assert_eq!(read_string(buffer, 4).unwrap(), "");
+    assert!(read_string(buffer, 5).is_err());
+    assert!(read_string(buffer, 0x1000).is_err());
+}
+
+#[test]
+fn relocation_info_round_trip() {
+    let relocation = Relocation::with_symbol(0x10, 0x1234, R_PPC_ADDR32, 0);
+    
+    assert_eq!(relocation.info, 0x123401);
+    assert_eq!(relocation.symbol_index(), 0x1234);
+    assert_eq!(relocation.reloc_type(), R_PPC_ADDR32);
+}
+
+#[test]
+fn debug_pointer_matches_linked_section() {
+    let mut expected = Cursor::new(vec![0; 8]);
+    expected.set_position(4);
+    ElfWriteDomain::new(0, true).write_pointer_debug(&mut expected, Pointer(0x18)).unwrap();
+    
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader::default(), String::new()));
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x10, ..Default::default() }, "target".to_owned()));
+    
+    let section = Section {
+        relocations: Some([(Pointer(4), Relocation::with_symbol(4, 1, R_PPC_ADDR32, 8))].into_iter().collect()),
+        content: vec![0; 8],
+        ..Default::default()
+    };
+    
+    let linked = link_section_debug(&section, &symbols).unwrap();
+    assert_eq!(linked, expected.into_inner());
+    assert_eq!(linked[4..], (0x18 | DEBUG_RELOCATION_TAG).to_be_bytes());
+}
+
+#[test]
+fn linking_malformed_relocations_fails() {
+    let mut symbols = SymbolTable::new();
+    symbols.push(Symbol::new(SymbolHeader { st_value: 0x10, ..Default::default() }, "target".to_owned()));
+    
+    let mut section = Section {
+        relocations: Some([(Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, u32::MAX))].into_iter().collect()),
+        content: vec![0; 4],
+        ..Default::default()
+    };
+    assert!(link_section_debug(&section, &symbols).is_err());
+    
+    section.relocations = Some([(Pointer(0), Relocation::with_symbol(0, 0, R_PPC_ADDR32, 0))].into_iter().collect());
+    section.content = vec![0, 0, 0, 1];
+    assert!(link_section_debug(&section, &symbols).is_err());
+}
+
+#[test]
+fn write_symbol_trailing_alignment() {
+    let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
+    let mut domain = ElfWriteDomain::new(0, false);
+    
+    let args = WriteSymbolArgs { trailing_alignment: Some(0x10) };
+    domain.write_symbol_with_args(&mut ctx, "first", args, |domain, ctx| {
+        1u32.to_writer(ctx, domain)
+    }).unwrap();
+    domain.write_symbol(&mut ctx, "second", |domain, ctx| {
+        2u32.to_writer(ctx, domain)
+    }).unwrap();
+    
+    assert_eq!(domain.symbol_declarations[0].size, 4);
+    assert_eq!(domain.symbol_declarations[1].size, 4);
+    assert_eq!(ctx.position().unwrap(), 0x14);
+}
+
+#[test]
+fn wide_values_round_trip() {
+    #[derive(Debug, PartialEq)]
+    struct Wide {
+        value: f64,
+        id: u64,
+    }
+    
+    impl Readable<ElfReadDomain<'_>> for Wide {
+        fn from_reader_unboxed<R: vivibin::Reader>(reader: &mut R, domain: ElfReadDomain<'_>) -> Result<Self> {
+            Ok(Self {
+                value: domain.read(reader)?,
+                id: domain.read(reader)?,
+            })
+        }
+    }
+    
+    let wide = Wide { value: -1234.5678e100, id: 0x0123_4567_89ab_cdef };
+    
+    let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
+    let mut domain = ElfWriteDomain::new(0, false);
+    assert_eq!(domain.content_alignment, 4);
+    domain.write(&mut ctx, &wide.value).unwrap();
+    domain.write(&mut ctx, &wide.id).unwrap();
+    assert_eq!(domain.content_alignment, 8);
+    
+    let mut resolver = HeapResolver::default();
+    let heap_id = ctx.heap_id_of(DataCategory::Rodata);
+    resolver.write_heap(&mut domain, heap_id, ctx.heap(&DataCategory::Rodata).unwrap()).unwrap();
+    let content = resolver.output_buffers[&heap_id].borrow().get_ref().clone();
+    
+    let mut expected = wide.value.to_be_bytes().to_vec();
+    expected.extend_from_slice(&wide.id.to_be_bytes());
+    assert_eq!(content, expected);
+    
+    let symbols = SymbolTable::new();
+    let relocations = Default::default();
+    let state = ReadState::default();
+    let read_domain = ElfReadDomain::new(&content, &relocations, &symbols, &state);
+    assert_eq!(Wide::from_reader(&mut Cursor::new(&content[..]), read_domain).unwrap(), wide);
+}
+
+#[test]
+fn fixed_arrays_are_written_inline() {
+    let position = [1.5f32, -2.0, 0.25];
+    
+    let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
+    let mut domain = ElfWriteDomain::new(0, false);
+    domain.write_fixed_array(&mut ctx, &position, |domain, ctx, value| value.to_writer(ctx, domain)).unwrap();
+    
+    let mut resolver = HeapResolver::default();
+    let heap_id = ctx.heap_id_of(DataCategory::Rodata);
+    resolver.write_heap(&mut domain, heap_id, ctx.heap(&DataCategory::Rodata).unwrap()).unwrap();
+    let content = resolver.output_buffers[&heap_id].borrow().get_ref().clone();
+    
+    assert_eq!(content.len(), 12);
+    assert!(domain.relocations.is_empty());
+    assert_eq!(content[..4], 1.5f32.to_be_bytes());
+    
+    let symbols = SymbolTable::new();
+    let relocations = Default::default();
+    let state = ReadState::default();
+    let read_domain = ElfReadDomain::new(&content, &relocations, &symbols, &state);
+    let read: [f32; 3] = read_domain.read_fixed_array(&mut Cursor::new(&content[..]), |reader| {
+        f32::from_reader(reader, read_domain)
+    }).unwrap();
+    assert_eq!(read, position);
+}
+
+#[test]
+fn resolved_relocations_point_into_symbols() {
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let elf_file = ElfContainer::from_reader(&mut Cursor::new(&input_file[..])).unwrap();
+    
+    let section = elf_file.get_section(FileType::Maplink.content_section_name()).unwrap();
+    let relocation_count = section.relocations.as_ref().unwrap().len();
+    
+    let resolved: Vec<_> = section.resolved_relocations(&elf_file.symbols).collect();
+    assert_eq!(resolved.len(), relocation_count);
+    
+    for (offset, symbol, addend) in resolved {
+        let relocation = &section.relocations.as_ref().unwrap()[&offset];
+        assert_eq!(elf_file.symbols.get_index(relocation.symbol_index() as usize).unwrap().name, symbol.name);
+        assert_eq!(addend, relocation.addend);
+    }
+}
+
+#[test]
+fn file_symbol_can_be_omitted() {
+    let (symtab, _, strtab) = write_symtab(Some("data_test.cpp"), &[], &mut HashMap::new(), &mut Vec::new(), |_| 1, false).unwrap();
+    assert_eq!(strtab, b"\0data_test.cpp\0");
+    let symtab_with_file = symtab.len();
+    
+    let (symtab, _, strtab) = write_symtab(None, &[], &mut HashMap::new(), &mut Vec::new(), |_| 1, false).unwrap();
+    assert_eq!(strtab, b"\0");
+    assert_eq!(symtab.len() + size_of::<SymbolHeader>(), symtab_with_file);
+    
+    assert_eq!(FileType::Dispos.cpp_file_name(), None);
+}
+
+#[test]
+fn symbol_prefixes_are_valid() {
+    for prefix in [STRING_SYMBOL_PREFIX, SHOP_ITEMS_SYMBOL_PREFIX, BOX_SYMBOL_PREFIX] {
+        assert!(is_valid_symbol_prefix(prefix), "{prefix:?}");
+    }
+    
+    assert!(!is_valid_symbol_prefix('ä'));
+    assert!(!is_valid_symbol_prefix(' '));
+}
+
+#[test]
+fn yaz0_round_trip() {
+    let packed = b"Yaz0\0\0\0\x06\0\0\0\0\0\0\0\0\xc0ab\x20\x01";
+    assert!(yaz0::is_yaz0(packed));
+    assert_eq!(yaz0::decompress(packed).unwrap(), b"ababab");
+    
+    let input_file = fs::read("test/data_fld_maplink.elf").unwrap();
+    let compressed = yaz0::compress(&input_file);
+    assert!(compressed.len() < input_file.len());
+    assert_eq!(yaz0::decompress(&compressed).unwrap(), input_file);
+    
+    assert!(yaz0::decompress(&compressed[..compressed.len() / 2]).is_err());
+}
+
+#[test]
+fn pointer_alignment() {
+    assert!(Pointer(0x10).is_aligned(4));
+    assert!(!Pointer(0x12).is_aligned(4));
+    assert_eq!(Pointer(0x12).align_up(4), Pointer(0x14));
+    assert_eq!(Pointer(0x14).align_up(4), Pointer(0x14));
+    assert_eq!(Pointer(0x11).align_up(0x10), Pointer(0x20));
+}
+
+#[test]
+fn pointer_byte_order() {
+    let mut writer = Cursor::new(Vec::new());
+    Pointer(0x12345678).write(&mut writer).unwrap();
+    Pointer::write_option(None, &mut writer).unwrap();
+    Pointer(0x10).write(&mut writer).unwrap();
+    0u32.write_be(&mut writer).unwrap();
+    
+    let bytes = writer.into_inner();
+    assert_eq!(bytes[..4], [0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(bytes.len(), 12);
+    
+    let mut reader = Cursor::new(&bytes[..]);
+    assert_eq!(Pointer::read(&mut reader).unwrap(), Some(Pointer(0x12345678)));
+    // relative to the position of the pointer itself
+    assert_eq!(Pointer::read_relative(&mut reader).unwrap(), Some(Pointer(0x14)));
+    assert_eq!(Pointer::read(&mut reader).unwrap(), None);
+}
+
+/// Writes `value`, checks its size and reads it back, also making sure that writing it again gives the same bytes.
+fn binary_round_trip<T>(value: &T, expected_size: usize) -> T
+where
+    T: ReadEndian + WriteEndian + for<'a> BinRead<Args<'a> = ()> + for<'a> BinWrite<Args<'a> = ()>,
+{
+    let mut writer = Cursor::new(Vec::new());
+    value.write(&mut writer).unwrap();
+    let bytes = writer.into_inner();
+    assert_eq!(bytes.len(), expected_size);
+    
+    let read_back = T::read(&mut Cursor::new(&bytes[..])).unwrap();
+    let mut writer = Cursor::new(Vec::new());
+    read_back.write(&mut writer).unwrap();
+    assert_eq!(writer.into_inner(), bytes);
+    
+    read_back
+}
+
+#[test]
+fn elf_header_layout() {
+    let header = ElfHeader {
+        e_ident: ELF_HEADER_IDENT,
+        e_type: ET_REL,
+        e_machine: EM_PPC,
+        e_version: 1,
+        e_entry: 0x11223344,
+        e_phoff: 0,
+        e_shoff: 0x1234,
+        e_flags: EF_PPC_EMB,
+        e_ehsize: 0x34,
+        e_phentsize: 0,
+        e_phnum: 0,
+        e_shentsize: 0x28,
+        e_shnum: 6,
+        e_shstrndx: 3,
+    };
+    assert_eq!(size_of::<ElfHeader>(), 0x34);
+    
+    let read_back = binary_round_trip(&header, 0x34);
+    assert_eq!(read_back.e_ident, ELF_HEADER_IDENT);
+    assert_eq!((read_back.e_type, read_back.e_machine, read_back.e_version), (ET_REL, EM_PPC, 1));
+    assert_eq!((read_back.e_entry, read_back.e_shoff, read_back.e_flags), (0x11223344, 0x1234, EF_PPC_EMB));
+    assert_eq!((read_back.e_ehsize, read_back.e_shentsize), (0x34, 0x28));
+    assert_eq!((read_back.e_shnum, read_back.e_shstrndx), (6, 3));
+}
+
+#[test]
+fn section_header_layout() {
+    let header = SectionHeader {
+        sh_name: 1,
+        sh_type: SectionType::Rela,
+        sh_flags: 0x40,
+        sh_addr: 2,
+        sh_offset: 0x34,
+        sh_size: 0x18,
+        sh_link: 4,
+        sh_info: 1,
+        sh_addralign: 4,
+        sh_entsize: 0xc,
+    };
+    
+    let read_back = binary_round_trip(&header, 0x28);
+    assert_eq!((read_back.sh_name, read_back.sh_type, read_back.sh_flags), (1, SectionType::Rela, 0x40));
+    assert_eq!((read_back.sh_addr, read_back.sh_offset, read_back.sh_size), (2, 0x34, 0x18));
+    assert_eq!((read_back.sh_link, read_back.sh_info), (4, 1));
+    assert_eq!((read_back.sh_addralign, read_back.sh_entsize), (4, 0xc));
+}
+
+#[test]
+fn symbol_header_layout() {
+    let header = SymbolHeader {
+        st_name: 0x10,
+        st_value: 0x20,
+        st_size: 0x30,
+        st_info: 0x11,
+        st_other: 2,
+        st_shndx: 0xfff1,
+    };
+    assert_eq!(size_of::<SymbolHeader>(), 0x10);
+    
+    let read_back = binary_round_trip(&header, 0x10);
+    assert_eq!((read_back.st_name, read_back.st_value, read_back.st_size), (0x10, 0x20, 0x30));
+    assert_eq!((read_back.st_info, read_back.st_other, read_back.st_shndx), (0x11, 2, 0xfff1));
+}
+
+#[test]
+fn relocation_layout() {
+    let relocation = Relocation::with_symbol(0x24, 3, R_PPC_ADDR32, 8);
+    assert_eq!(size_of::<Relocation>(), 0xc);
+    
+    let read_back = binary_round_trip(&relocation, 0xc);
+    assert_eq!(read_back.offset, 0x24);
+    assert_eq!((read_back.symbol_index(), read_back.reloc_type()), (3, R_PPC_ADDR32));
+    assert_eq!(read_back.addend, 8);
+}
+
+#[test]
+fn strings_past_dedup_size_are_counted() {
+    let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
+    let mut domain = ElfWriteDomain::new(8, false);
+    
+    for value in ["first", "first", "second", "first", "second"] {
+        domain.write_string(&mut ctx, value, WriteStringArgs::default()).unwrap();
+    }
+    
+    assert_eq!(domain.strings_deduplicated, 1);
+    assert_eq!(domain.strings_past_dedup_size, 2);
+}
+
+#[test]
+fn string_dedup_can_be_turned_off() {
+    let mut ctx: WriteCtxImpl<DataCategory> = ElfWriteDomain::new_ctx(DataCategory::Rodata);
+    let mut domain = ElfWriteDomain::new(u64::MAX, false);
+    domain.deduplicate_strings = false;
+    
+    for value in ["first", "first"] {
+        domain.write_string(&mut ctx, value, WriteStringArgs::default()).unwrap();
+    }
+    
+    assert_eq!(domain.strings_deduplicated, 0);
+    assert_eq!(domain.strings_written, 2);
+}
+
+#[test]
+fn string_dedup_size_can_be_overridden() {
+    let input_file = fs::read("test/data_fld_mapid.elf").unwrap();
+    let data = disassemble_bytes(&input_file, FileType::MapId).unwrap();
+    
+    let mut stats = ReassembleStats::default();
+    let options = ReassembleOptions { string_dedup_size: Some(0), ..Default::default() };
+    reassemble_elf_container_with_options(&data, options, Some(&mut stats)).unwrap();
+    assert_eq!(stats.strings_deduplicated, 0);
+    
+    let mut default_stats = ReassembleStats::default();
+    reassemble_elf_container(&data, Some(&mut default_stats)).unwrap();
+    assert!(default_stats.strings_deduplicated > 0);
+    assert_eq!(stats.strings_past_dedup_size, default_stats.strings_deduplicated + default_stats.strings_past_dedup_size);
+}
+
+#[test]
+fn compact_yaml_keeps_content() {
+    let yaml = indoc! {"
+        - id: test
+          field_0x4: 1
+          field_0x8: -2.5
+          links:
+          - name: 'a, b'
+            value: 0x10
+          - name: plain, with comma
+            value: 3
+        - id: other
+          field_0x4: 0
+    "};
+    
+    let compacted = compact_yaml(yaml);
+    
+    assert_eq!(compacted, indoc! {"
+        - id: test
+          field_0x4: 1
+          field_0x8: -2.5
+          links:
+          - {name: 'a, b', value: 0x10}
+          - name: plain, with comma
+            value: 3
+        - {id: other, field_0x4: 0}
+    "});
+    
+    let original: serde_yaml_bw::Value = serde_yaml_bw::from_str(yaml).unwrap();
+    let reparsed: serde_yaml_bw::Value = serde_yaml_bw::from_str(&compacted).unwrap();
+    assert_eq!(original, reparsed);
+}
+
+#[test]
+fn merge_file_data() {
+    fn area(id: &str, map_id: &str) -> DisposArea {
+        DisposArea {
+            id: id.to_string(),
+            map_npcs: vec![DisposNpc { map_id: map_id.to_string(), npcs: Vec::new() }],
+            map_mobjs: Vec::new(),
+            map_items: Vec::new(),
+        }
+    }
+    
+    let data = FileData::Dispos(vec![area("a", "a1"), area("b", "b1")]);
+    let other = FileData::Dispos(vec![area("b", "b2"), area("c", "c1")]);
+    
+    let merged = data.clone().try_merge(other.clone(), false).unwrap();
+    assert_eq!(merged.as_dispos().unwrap(), [area("a", "a1"), area("b", "b1"), area("b", "b2"), area("c", "c1")]);
+    
+    let merged = data.clone().try_merge(other, true).unwrap();
+    assert_eq!(merged.as_dispos().unwrap(), [area("a", "a1"), area("b", "b2"), area("c", "c1")]);
+    
+    assert!(data.try_merge(FileData::Shop(Vec::new()), false).is_err());
+}
+
+#[test]
+fn file_data_from_and_accessors() {
+    let data = FileData::from(Vec::<Shop>::new());
+    
+    assert_eq!(data, FileData::Shop(Vec::new()));
+    assert_eq!(data.as_shop(), Some([].as_slice()));
+    assert!(data.as_maplink().is_none());
+    assert!(data.as_chr().is_none());
+}
+
+#[test]
+fn relocations_target_symbols_in_their_section() {
+    let mut symbol_indices = HashMap::new();
+    symbol_indices.insert((1, 0x0), 3);
+    symbol_indices.insert((1, 0x10), 4);
+    symbol_indices.insert((3, 0x0), 5);
+    let section_index_of = |category: Option<DataCategory>| if category == Some(DataCategory::Rodata) { 3 } else { 1 };
+    
+    let mut relocations = [
+        RelDeclaration { base_location: 0x20, target_location: 0x10, addend: 0, target_category: None },
+        RelDeclaration { base_location: 0x24, target_location: 0x0, addend: 0, target_category: Some(DataCategory::Rodata) },
+        // stays relative to the first symbol even though it points into the second one
+        RelDeclaration { base_location: 0x28, target_location: 0x0, addend: 0x14, target_category: None },
+    ];
+    let raw = write_relocations(&symbol_indices, section_index_of, &mut relocations).unwrap();
+    
+    let mut reader = Cursor::new(raw.as_slice());
+    let first = Relocation::read(&mut reader).unwrap();
+    let second = Relocation::read(&mut reader).unwrap();
+    let third = Relocation::read(&mut reader).unwrap();
+    
+    assert_eq!((first.offset, first.symbol_index(), first.addend), (0x20, 4, 0));
+    assert_eq!((second.offset, second.symbol_index(), second.addend), (0x24, 5, 0));
+    assert_eq!((third.offset, third.symbol_index(), third.addend), (0x28, 3, 0x14));
+    
+    // there is no symbol at 0x18 and none at 0x10 of the other section
+    let mut relocations = [RelDeclaration { base_location: 0x20, target_location: 0x18, ..Default::default() }];
+    assert!(write_relocations(&symbol_indices, section_index_of, &mut relocations).is_err());
+    let mut relocations = [RelDeclaration {
+        base_location: 0x20, target_location: 0x10, addend: 0, target_category: Some(DataCategory::Rodata),
+    }];
+    assert!(write_relocations(&symbol_indices, section_index_of, &mut relocations).is_err());
+}
+
+#[test]
+fn file_type_from_string_aliases() {
+    assert_eq!(FileType::from_string("maplink"), Some(FileType::Maplink));
+    assert_eq!(FileType::from_string("Maplink"), Some(FileType::Maplink));
+    assert_eq!(FileType::from_string("map_link"), Some(FileType::Maplink));
+    assert_eq!(FileType::from_string("MAPID"), Some(FileType::MapId));
+    assert_eq!(FileType::from_string("Map_Id"), Some(FileType::MapId));
+    assert_eq!(FileType::from_string("shops"), Some(FileType::Shop));
+    assert_eq!(FileType::from_string("unknown"), None);
+    
+    assert_eq!("Map_Link".parse::<FileType>(), Ok(FileType::Maplink));
+    let err = "unknown".parse::<FileType>().unwrap_err();
+    assert_eq!(err, UnknownFileType("unknown".to_owned()));
+    assert!(err.to_string().contains("maplink, mapid"));
+}
@@ -0,0 +1,52 @@
+//! Serde helpers for `f32` fields that have to survive a yaml round trip bit for bit,
+//! to be used as `#[serde(with = "crate::util::exact_f32")]`.
+//!
+//! Regular values are written as plain floats, whose shortest representation already parses
+//! back to the same bits. NaNs would all turn into `.nan` though, so they are written as
+//! a string containing their raw bits instead, e.g. `'0x7fc00001'`.
+
+use core::fmt;
+
+use serde::{Deserializer, Serializer, de::{self, Visitor}};
+
+pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.is_nan() {
+        serializer.serialize_str(&format!("0x{:08x}", value.to_bits()))
+    } else {
+        serializer.serialize_f32(*value)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    deserializer.deserialize_any(ExactF32Visitor)
+}
+
+struct ExactF32Visitor;
+
+impl Visitor<'_> for ExactF32Visitor {
+    type Value = f32;
+    
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a float or a string with the raw bits of a float")
+    }
+    
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<f32, E> {
+        Ok(value as f32)
+    }
+    
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<f32, E> {
+        Ok(value as f32)
+    }
+    
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<f32, E> {
+        Ok(value as f32)
+    }
+    
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<f32, E> {
+        let bits = value.strip_prefix("0x")
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))?;
+        
+        Ok(f32::from_bits(bits))
+    }
+}
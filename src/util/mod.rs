@@ -1,9 +1,15 @@
 use core::ffi::CStr;
-use std::{io::{Read, Seek, SeekFrom}};
+use std::{fs, io::{Read, Seek, SeekFrom}, path::Path};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use log::warn;
 
+use crate::error::PaintelfError;
+
+pub mod exact_f32;
 pub mod pointer;
+pub mod yaml;
+pub mod yaz0;
 
 // scoped reader pos
 pub struct ReaderGuard<'a, R: Read + Seek> {
@@ -12,33 +18,78 @@ pub struct ReaderGuard<'a, R: Read + Seek> {
 }
 
 impl<'a, R: Read + Seek> ReaderGuard<'a, R> {
-    pub fn new(reader: &'a mut R) -> Self {
-        let start_pos = reader.stream_position().unwrap();
+    pub fn new(reader: &'a mut R) -> Result<Self> {
+        let start_pos = reader.stream_position()?;
         
-        Self {
+        Ok(Self {
             reader,
             start_pos,
-        }
+        })
     }
 }
 
 impl<'a, R: Read + Seek> Drop for ReaderGuard<'a, R> {
     fn drop(&mut self) {
-        self.reader.seek(SeekFrom::Start(self.start_pos)).unwrap();
+        // seeking to a position that was valid before can only fail on a broken reader,
+        // so don't panic here as this may run during unwinding
+        if let Err(err) = self.reader.seek(SeekFrom::Start(self.start_pos)) {
+            warn!("Could not restore reader position 0x{:x}: {err}", self.start_pos);
+        }
     }
 }
 
 #[macro_export]
 macro_rules! scoped_reader_pos {
     ($reader:ident) => {
-        let guard = $crate::util::ReaderGuard::new($reader);
+        let guard = $crate::util::ReaderGuard::new($reader)?;
         let $reader = &mut *guard.reader;
     };
 }
 
 // string utils
 pub fn read_string(buffer: &[u8], index: u32) -> Result<&str> {
-    let bytes = &buffer[index as usize..];
+    let bytes = buffer.get(index as usize..)
+        .ok_or(PaintelfError::PointerOutOfBounds { offset: index, len: buffer.len() })?;
     let result = CStr::from_bytes_until_nul(bytes)?.to_str()?;
     Ok(result)
 }
+
+/// Levenshtein distance between `a` and `b`, counted in chars.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    
+    row[b.len()]
+}
+
+// file utils
+/// Writes `contents` into a `.tmp` sibling of `path` first and then renames it to `path`,
+/// so that `path` never ends up containing a partially written file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let mut tmp_name = path.file_name()
+        .ok_or_else(|| anyhow!("Invalid file path {}", path.display()))?
+        .to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    
+    let result = fs::write(&tmp_path, contents).and_then(|_| fs::rename(&tmp_path, path));
+    
+    if result.is_err() {
+        // the temp file may not exist at all if writing failed early
+        let _ = fs::remove_file(&tmp_path);
+    }
+    
+    result?;
+    Ok(())
+}
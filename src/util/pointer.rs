@@ -5,7 +5,7 @@ use std::{io::{Cursor, Read, Seek, Write}};
 use anyhow::Result;
 use binrw::{BinRead, BinWrite};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{self, Visitor}};
 
 macro_rules! from_type {
     ($t:ident, $from:ty) => {
@@ -113,6 +113,16 @@ impl Pointer {
         }
     }
     
+    /// Whether this pointer is a multiple of `align`.
+    pub fn is_aligned(&self, align: u32) -> bool {
+        self.0.is_multiple_of(align)
+    }
+    
+    /// Rounds this pointer up to the next multiple of `align`.
+    pub fn align_up(self, align: u32) -> Pointer {
+        Pointer(self.0.next_multiple_of(align))
+    }
+    
     pub fn write(&self, writer: &mut impl Write) -> Result<()> {
         writer.write_u32::<BigEndian>(self.0)?;
         Ok(())
@@ -148,6 +158,65 @@ impl Sub<Self> for Pointer {
     }
 }
 
+/// Pointer that is written into yaml as `"@symbol_name"` if it points to the start of a named symbol,
+/// so that it keeps pointing to that symbol when the file is rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SymbolPointer {
+    Symbol(String),
+    Offset(Pointer),
+}
+
+impl Default for SymbolPointer {
+    /// A null pointer
+    fn default() -> Self {
+        SymbolPointer::Offset(Pointer::default())
+    }
+}
+
+impl Serialize for SymbolPointer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        match self {
+            SymbolPointer::Symbol(name) => serializer.serialize_str(&format!("@{name}")),
+            SymbolPointer::Offset(pointer) => serializer.serialize_u32(pointer.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SymbolPointer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        deserializer.deserialize_any(SymbolPointerVisitor)
+    }
+}
+
+struct SymbolPointerVisitor;
+
+impl Visitor<'_> for SymbolPointerVisitor {
+    type Value = SymbolPointer;
+    
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an offset or a symbol name starting with '@'")
+    }
+    
+    fn visit_u64<E: de::Error>(self, value: u64) -> result::Result<SymbolPointer, E> {
+        let offset = u32::try_from(value)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))?;
+        Ok(SymbolPointer::Offset(Pointer(offset)))
+    }
+    
+    fn visit_i64<E: de::Error>(self, value: i64) -> result::Result<SymbolPointer, E> {
+        let offset = u32::try_from(value)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))?;
+        Ok(SymbolPointer::Offset(Pointer(offset)))
+    }
+    
+    fn visit_str<E: de::Error>(self, value: &str) -> result::Result<SymbolPointer, E> {
+        match value.strip_prefix('@') {
+            Some(name) if !name.is_empty() => Ok(SymbolPointer::Symbol(name.to_owned())),
+            _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+        }
+    }
+}
+
 impl<T> TryFrom<&Cursor<T>> for Pointer {
     type Error = TryFromIntError;
 
@@ -0,0 +1,97 @@
+/// Rewrites block style yaml so that every mapping consisting only of simple scalar values
+/// is written as a single line flow mapping, e.g. `{field_0x8: 0, field_0xc: 1.5}`.
+///
+/// Mappings containing nested values, block scalars or plain scalars that would change meaning
+/// inside of a flow collection are left untouched.
+pub fn compact_yaml(yaml: &str) -> String {
+    let lines: Vec<&str> = yaml.lines().collect();
+    let mut result = String::with_capacity(yaml.len());
+    
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some((compacted, line_count)) = compact_mapping_at(&lines, i) {
+            result.push_str(&compacted);
+            result.push('\n');
+            i += line_count;
+        } else {
+            result.push_str(lines[i]);
+            result.push('\n');
+            i += 1;
+        }
+    }
+    
+    result
+}
+
+/// Tries to compact the mapping starting at `lines[start]`, returning the new line
+/// and how many lines it replaces.
+fn compact_mapping_at(lines: &[&str], start: usize) -> Option<(String, usize)> {
+    let line = lines[start];
+    let indent = indentation(line);
+    let content = &line[indent..];
+    
+    // only start at the first entry of a mapping
+    let (prefix, first_entry, key_indent) = if let Some(first_entry) = content.strip_prefix("- ") {
+        (&line[..indent + 2], first_entry, indent + 2)
+    } else if start == 0 || content_indentation(lines[start - 1]) < indent {
+        (&line[..indent], content, indent)
+    } else {
+        return None;
+    };
+    
+    let mut entries = vec![simple_entry(first_entry)?];
+    
+    for line in &lines[start + 1..] {
+        let line_indent = indentation(line);
+        
+        if line_indent < key_indent || (line_indent == key_indent && line[line_indent..].starts_with("- ")) {
+            break;
+        }
+        
+        if line_indent > key_indent {
+            return None;
+        }
+        
+        entries.push(simple_entry(&line[line_indent..])?);
+    }
+    
+    if entries.len() < 2 {
+        return None;
+    }
+    
+    let line_count = entries.len();
+    let entries: Vec<String> = entries.into_iter()
+        .map(|(key, value)| format!("{key}: {value}"))
+        .collect();
+    
+    Some((format!("{prefix}{{{}}}", entries.join(", ")), line_count))
+}
+
+fn simple_entry(entry: &str) -> Option<(&str, &str)> {
+    let (key, value) = entry.split_once(": ")?;
+    
+    let is_simple_key = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let is_quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"')) || (value.starts_with('\'') && value.ends_with('\'')));
+    let is_simple_plain = !value.is_empty()
+        && !value.starts_with(['|', '>', '&', '*', '!', '\'', '"'])
+        && !value.contains([',', '[', ']', '{', '}', '#'])
+        && !value.contains(": ");
+    
+    (is_simple_key && (is_quoted || is_simple_plain)).then_some((key, value))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Indentation of the content of a line, skipping over the sequence indicator if there is one.
+fn content_indentation(line: &str) -> usize {
+    let indent = indentation(line);
+    
+    if line[indent..].starts_with("- ") {
+        indent + 2
+    } else {
+        indent
+    }
+}
@@ -0,0 +1,158 @@
+//! Yaz0, the compression format many of the game's files are packed with.
+//!
+//! The data is split into groups of up to eight chunks, each preceded by a byte whose bits
+//! (most significant first) say whether the chunk is a literal byte or a back reference
+//! into the already decompressed data.
+
+use anyhow::{Result, bail, ensure};
+use vivibin::util::HashMap;
+
+pub const YAZ0_MAGIC: &[u8; 4] = b"Yaz0";
+
+const HEADER_SIZE: usize = 0x10;
+const WINDOW_SIZE: usize = 0x1000;
+const MIN_MATCH_LEN: usize = 3;
+const MAX_MATCH_LEN: usize = 0x111;
+/// Amount of earlier positions to try for each match, trading compression ratio for speed
+const MAX_MATCH_CANDIDATES: usize = 64;
+
+pub fn is_yaz0(bytes: &[u8]) -> bool {
+    bytes.starts_with(YAZ0_MAGIC)
+}
+
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    ensure!(is_yaz0(bytes) && bytes.len() >= HEADER_SIZE, "Not a Yaz0 compressed file");
+    
+    let size = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let mut result: Vec<u8> = Vec::with_capacity(size);
+    
+    let mut input = bytes[HEADER_SIZE..].iter().copied();
+    let mut next = || match input.next() {
+        Some(byte) => Ok(byte),
+        None => bail!("Unexpected end of Yaz0 data (expected 0x{size:x} bytes of decompressed data)"),
+    };
+    
+    while result.len() < size {
+        let code = next()?;
+        
+        for bit in (0..8).rev() {
+            if result.len() >= size {
+                break;
+            }
+            
+            if code & (1 << bit) != 0 {
+                let byte = next()?;
+                result.push(byte);
+                continue;
+            }
+            
+            let first = next()?;
+            let second = next()?;
+            
+            let distance = ((first as usize & 0xf) << 8 | second as usize) + 1;
+            let length = match first >> 4 {
+                0 => next()? as usize + 0x12,
+                n => n as usize + 2,
+            };
+            
+            ensure!(distance <= result.len(), "Yaz0 back reference of 0x{distance:x} bytes \
+                goes before the start of the data (at 0x{:x})", result.len());
+            
+            // the copied range may overlap with the bytes being written
+            let start = result.len() - distance;
+            for i in start..start + length {
+                result.push(result[i]);
+            }
+        }
+    }
+    
+    result.truncate(size);
+    Ok(result)
+}
+
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(HEADER_SIZE + bytes.len() + bytes.len() / 8 + 1);
+    result.extend_from_slice(YAZ0_MAGIC);
+    result.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    result.extend_from_slice(&[0; 8]);
+    
+    // earlier positions of every three byte sequence, most recent last
+    let mut positions: HashMap<[u8; 3], Vec<usize>> = HashMap::new();
+    let remember = |positions: &mut HashMap<[u8; 3], Vec<usize>>, pos: usize| {
+        if let Some(key) = bytes.get(pos..pos + 3) {
+            positions.entry(key.try_into().unwrap()).or_default().push(pos);
+        }
+    };
+    
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let code_index = result.len();
+        result.push(0);
+        
+        for bit in (0..8).rev() {
+            if pos >= bytes.len() {
+                break;
+            }
+            
+            let (match_pos, match_len) = find_match(bytes, pos, &positions);
+            
+            if match_len < MIN_MATCH_LEN {
+                result[code_index] |= 1 << bit;
+                result.push(bytes[pos]);
+                remember(&mut positions, pos);
+                pos += 1;
+                continue;
+            }
+            
+            let distance = pos - match_pos - 1;
+            if match_len >= 0x12 {
+                result.push((distance >> 8) as u8);
+                result.push(distance as u8);
+                result.push((match_len - 0x12) as u8);
+            } else {
+                result.push(((match_len - 2) << 4 | distance >> 8) as u8);
+                result.push(distance as u8);
+            }
+            
+            for i in pos..pos + match_len {
+                remember(&mut positions, i);
+            }
+            pos += match_len;
+        }
+    }
+    
+    result
+}
+
+/// Finds the longest earlier occurrence of the bytes at `pos` within the window.
+fn find_match(bytes: &[u8], pos: usize, positions: &HashMap<[u8; 3], Vec<usize>>) -> (usize, usize) {
+    let Some(key) = bytes.get(pos..pos + 3) else {
+        return (0, 0);
+    };
+    let Some(candidates) = positions.get(key) else {
+        return (0, 0);
+    };
+    
+    let max_len = MAX_MATCH_LEN.min(bytes.len() - pos);
+    let mut best = (0, 0);
+    
+    for &candidate in candidates.iter().rev().take(MAX_MATCH_CANDIDATES) {
+        if pos - candidate > WINDOW_SIZE {
+            break;
+        }
+        
+        let len = (0..max_len)
+            .take_while(|&i| bytes[candidate + i] == bytes[pos + i])
+            .count();
+        
+        if len > best.1 {
+            best = (candidate, len);
+            
+            if len == max_len {
+                break;
+            }
+        }
+    }
+    
+    best
+}